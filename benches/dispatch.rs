@@ -0,0 +1,124 @@
+//! Benchmarks of the client's hot paths against an in-process server
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::MockServer;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rcpcli::{command, AppList, Client, ServiceClient, ServiceType};
+use rcpcore::{CommandId, Frame, Protocol};
+use std::sync::Arc;
+use tokio::{net::TcpStream, runtime::Runtime, sync::Mutex};
+use uuid::Uuid;
+
+/// Stream frames sent per iteration of the dispatch benchmark
+const FRAMES_PER_BATCH: u64 = 256;
+
+/// Payload sizes of the stream frames: small ones, and ones as large as an
+/// encoded video frame
+const FRAME_PAYLOAD_LENS: [(&str, usize); 2] = [
+    ("display_stream_frames", 64),
+    ("display_large_frames", 256 * 1024),
+];
+
+/// Connect a client and subscribe it to `service_type`, returning the
+/// server's end of the connection
+async fn connect(service_type: ServiceType) -> (Client, ServiceClient, Protocol<TcpStream>) {
+    let server = MockServer::bind().await;
+    let port = server.port();
+    let server_task = tokio::spawn(async move { server.accept_authenticated().await });
+
+    let client = Client::builder()
+        .host("127.0.0.1")
+        .port(port)
+        .auth_psk("bench-key")
+        .auto_reconnect(false)
+        .build();
+    client.connect_and_authenticate().await.unwrap();
+    let mut server_conn = server_task.await.unwrap();
+    client.start().await.unwrap();
+
+    let service = client.subscribe_service(service_type).await.unwrap();
+    let subscription = server_conn.read_frame().await.unwrap().unwrap();
+    assert_eq!(
+        subscription.command_id(),
+        service_type.subscription_command()
+    );
+    (client, service, server_conn)
+}
+
+/// Inbound stream frames dispatched to a subscribed display service
+fn bench_display_dispatch(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (client, _display, server_conn) = rt.block_on(connect(ServiceType::Display));
+    let server_conn = Arc::new(Mutex::new(server_conn));
+    let ack = Frame::new(
+        CommandId::Ack as u8,
+        ServiceType::Display.to_string().into_bytes(),
+    );
+
+    let mut group = c.benchmark_group("dispatch");
+    group.throughput(Throughput::Elements(FRAMES_PER_BATCH));
+    for (name, payload_len) in FRAME_PAYLOAD_LENS {
+        let stream_frame = Frame::new(CommandId::StreamFrame as u8, vec![0xAB; payload_len]);
+        group.bench_function(name, |b| {
+            b.to_async(&rt).iter(|| async {
+                let mut conn = server_conn.lock().await;
+                for _ in 0..FRAMES_PER_BATCH {
+                    conn.write_frame(&stream_frame).await.unwrap();
+                }
+
+                // The display service asks for the display layout once it
+                // sees the acknowledgement, which it handles after the
+                // stream frames
+                conn.write_frame(&ack).await.unwrap();
+                loop {
+                    let frame = conn.read_frame().await.unwrap().unwrap();
+                    if frame.command_id() == CommandId::DisplayInfo as u8 {
+                        break;
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+
+    rt.block_on(client.disconnect()).unwrap();
+}
+
+/// Round trip of an App service request answered by the server
+fn bench_request_round_trip(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (client, app, mut server_conn) = rt.block_on(connect(ServiceType::App));
+
+    // Answer every app listing with an empty list
+    rt.spawn(async move {
+        while let Ok(Some(frame)) = server_conn.read_frame().await {
+            if frame.command_id() != command::LIST_APPS {
+                continue;
+            }
+            let request_id = Uuid::from_slice(&frame.payload()[..16]).unwrap();
+            let reply = AppList {
+                request_id,
+                apps: Vec::new(),
+            };
+            if server_conn
+                .write_frame(&reply.to_frame().unwrap())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    c.bench_function("request_round_trip", |b| {
+        b.to_async(&rt)
+            .iter(|| async { app.list_apps().await.unwrap() })
+    });
+
+    rt.block_on(client.disconnect()).unwrap();
+}
+
+criterion_group!(benches, bench_display_dispatch, bench_request_round_trip);
+criterion_main!(benches);
@@ -0,0 +1,140 @@
+//! Answering authentication challenges
+//!
+//! The server answers the auth payload with a challenge, which the client
+//! answers according to the configured [`AuthMethod`]. Each method is handled
+//! by an [`AuthHandler`], looked up in the client's [`AuthHandlers`], so new
+//! methods can be added without touching the handshake itself:
+//!
+//! ```
+//! use rcpcli::auth::{AuthContext, AuthHandler};
+//! use rcpcli::{Client, Error, Result};
+//! use rcpcore::{AuthChallenge, AuthMethod, AuthResponse};
+//!
+//! /// Answers with the password, for servers behind TLS that check it
+//! struct PlainPassword;
+//!
+//! impl AuthHandler for PlainPassword {
+//!     fn respond(&self, _challenge: &AuthChallenge, context: &AuthContext) -> Result<AuthResponse> {
+//!         let AuthMethod::Password(_, password) = &context.method else {
+//!             return Err(Error::Authentication("Expected a password".to_string()));
+//!         };
+//!         Ok(AuthResponse {
+//!             client_id: context.client_id,
+//!             response: password.as_bytes().to_vec(),
+//!         })
+//!     }
+//! }
+//!
+//! let client = Client::builder()
+//!     .auth_method(AuthMethod::Password("alice".to_string(), "secret".to_string()))
+//!     .auth_handler(AuthMethod::Password(String::new(), String::new()), PlainPassword)
+//!     .build();
+//! ```
+
+use crate::{
+    client::Credentials,
+    error::{Error, Result},
+};
+use rcpcore::{Auth, AuthChallenge, AuthMethod, AuthResponse};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::{self, Discriminant};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Everything an [`AuthHandler`] may need besides the challenge
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    /// Client ID sent in the auth payload, which the response must carry
+    pub client_id: Uuid,
+
+    /// Configured authentication method, with any data it carries
+    pub method: AuthMethod,
+
+    /// Credentials of this authentication, falling back to the configured
+    /// pre-shared key
+    pub credentials: Credentials,
+}
+
+/// Producer of the response to an authentication challenge
+pub trait AuthHandler: Send + Sync {
+    /// Compute the response to the server's challenge
+    ///
+    /// An error fails the authentication without dropping the connection.
+    fn respond(&self, challenge: &AuthChallenge, context: &AuthContext) -> Result<AuthResponse>;
+}
+
+/// Built-in handler for [`AuthMethod::PreSharedKey`]
+///
+/// Answers with rcpcore's keyed hash of the challenge and salt.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PskAuth;
+
+impl AuthHandler for PskAuth {
+    fn respond(&self, challenge: &AuthChallenge, context: &AuthContext) -> Result<AuthResponse> {
+        let psk = context
+            .credentials
+            .psk
+            .as_deref()
+            .ok_or_else(|| Error::Authentication("PSK not configured".to_string()))?;
+        Ok(AuthResponse {
+            client_id: context.client_id,
+            response: Auth::compute_psk_response(psk, &challenge.challenge, &challenge.salt),
+        })
+    }
+}
+
+/// Auth handlers by authentication method
+///
+/// Methods are told apart by their variant alone, so one handler covers
+/// every [`AuthMethod::Password`] whatever its username and password. The
+/// default registry handles [`AuthMethod::PreSharedKey`] with [`PskAuth`].
+#[derive(Clone)]
+pub struct AuthHandlers {
+    /// Handlers by method variant
+    handlers: HashMap<Discriminant<AuthMethod>, Arc<dyn AuthHandler>>,
+}
+
+impl AuthHandlers {
+    /// Create a registry without any handlers
+    pub fn empty() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Register the handler for a method, returning the one it replaces
+    ///
+    /// Only the variant of `method` matters, not the data it carries.
+    pub fn register(
+        &mut self,
+        method: AuthMethod,
+        handler: impl AuthHandler + 'static,
+    ) -> Option<Arc<dyn AuthHandler>> {
+        self.handlers
+            .insert(mem::discriminant(&method), Arc::new(handler))
+    }
+
+    /// Get the handler for a method
+    pub fn get(&self, method: &AuthMethod) -> Option<&dyn AuthHandler> {
+        self.handlers
+            .get(&mem::discriminant(method))
+            .map(|handler| handler.as_ref())
+    }
+}
+
+impl Default for AuthHandlers {
+    fn default() -> Self {
+        let mut handlers = Self::empty();
+        handlers.register(AuthMethod::PreSharedKey, PskAuth);
+        handlers
+    }
+}
+
+impl fmt::Debug for AuthHandlers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthHandlers")
+            .field("methods", &self.handlers.len())
+            .finish()
+    }
+}
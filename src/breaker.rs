@@ -0,0 +1,173 @@
+//! Circuit breaker for automatic reconnects
+//!
+//! While a server is down, the reconnect loop would otherwise try again
+//! after every reconnect delay for as long as the outage lasts. With a
+//! breaker, enough failed attempts within a rolling window open it: the
+//! client stops trying for a cooldown, then half-opens it and makes a single
+//! trial attempt. A successful trial closes the breaker again, a failed one
+//! reopens it for another cooldown. Sessions that die soon after they were
+//! established count as failed attempts, so a server that keeps accepting
+//! and dropping the client opens the breaker too.
+
+use crate::event::BreakerState;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Settings of the reconnect circuit breaker, see
+/// [`ClientBuilder::circuit_breaker`](crate::ClientBuilder::circuit_breaker)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Failed reconnect attempts within `window` that open the breaker
+    pub failure_threshold: u32,
+
+    /// Length of the rolling window failures are counted over
+    pub window: Duration,
+
+    /// How long the breaker stays open before a trial attempt
+    pub cooldown: Duration,
+}
+
+impl CircuitBreakerConfig {
+    /// Default number of failures opening the breaker
+    pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+    /// Default window failures are counted over
+    pub const DEFAULT_WINDOW: Duration = Duration::from_secs(60);
+
+    /// Default cooldown
+    pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(300);
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: Self::DEFAULT_FAILURE_THRESHOLD,
+            window: Self::DEFAULT_WINDOW,
+            cooldown: Self::DEFAULT_COOLDOWN,
+        }
+    }
+}
+
+/// State of the breaker, kept for the lifetime of the client
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    /// Settings
+    config: CircuitBreakerConfig,
+
+    /// Current state
+    state: BreakerState,
+
+    /// Times of the failures within the window, oldest first
+    failures: VecDeque<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Create a closed breaker
+    pub(crate) fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            state: BreakerState::Closed,
+            failures: VecDeque::new(),
+        }
+    }
+
+    /// How long the breaker stays open
+    pub(crate) fn cooldown(&self) -> Duration {
+        self.config.cooldown
+    }
+
+    /// Record a failed attempt at `now`, returning the new state if the
+    /// failure opened the breaker
+    pub(crate) fn record_failure(&mut self, now: Instant) -> Option<BreakerState> {
+        if self.state == BreakerState::HalfOpen {
+            return self.transition(BreakerState::Open);
+        }
+
+        self.failures.push_back(now);
+        while self
+            .failures
+            .front()
+            .is_some_and(|failure| now.duration_since(*failure) >= self.config.window)
+        {
+            self.failures.pop_front();
+        }
+        if self.failures.len() < self.config.failure_threshold.max(1) as usize {
+            return None;
+        }
+        self.transition(BreakerState::Open)
+    }
+
+    /// Record a successful attempt, returning the new state if it closed the
+    /// breaker
+    pub(crate) fn record_success(&mut self) -> Option<BreakerState> {
+        self.transition(BreakerState::Closed)
+    }
+
+    /// Allow a trial attempt once the cooldown is over, returning the new
+    /// state
+    pub(crate) fn half_open(&mut self) -> Option<BreakerState> {
+        self.transition(BreakerState::HalfOpen)
+    }
+
+    /// Move to `state`, forgetting earlier failures, and return it unless
+    /// the breaker was in it already
+    fn transition(&mut self, state: BreakerState) -> Option<BreakerState> {
+        if self.state == state {
+            return None;
+        }
+        self.state = state;
+        self.failures.clear();
+        Some(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 3,
+            window: Duration::from_secs(10),
+            cooldown: Duration::from_secs(60),
+        })
+    }
+
+    #[test]
+    fn test_opens_on_failures_within_window() {
+        let mut breaker = breaker();
+        let start = Instant::now();
+
+        // Failures spread wider than the window never add up
+        for i in 0..6 {
+            let now = start + Duration::from_secs(6 * i);
+            assert_eq!(breaker.record_failure(now), None);
+        }
+
+        let now = start + Duration::from_secs(40);
+        assert_eq!(breaker.record_failure(now), None);
+        assert_eq!(breaker.record_failure(now), None);
+        assert_eq!(breaker.record_failure(now), Some(BreakerState::Open));
+    }
+
+    #[test]
+    fn test_half_open_trial() {
+        let mut breaker = breaker();
+        let now = Instant::now();
+        for _ in 0..3 {
+            breaker.record_failure(now);
+        }
+
+        // A failed trial reopens the breaker right away
+        assert_eq!(breaker.half_open(), Some(BreakerState::HalfOpen));
+        assert_eq!(breaker.record_failure(now), Some(BreakerState::Open));
+
+        assert_eq!(breaker.half_open(), Some(BreakerState::HalfOpen));
+        assert_eq!(breaker.record_success(), Some(BreakerState::Closed));
+
+        // Closing starts the count over
+        assert_eq!(breaker.record_failure(now), None);
+        assert_eq!(breaker.record_success(), None);
+    }
+}
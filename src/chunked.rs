@@ -0,0 +1,249 @@
+//! Multi-frame messages
+//!
+//! Clipboard contents and file data can exceed the maximum frame size, so
+//! such messages are split into [`CHUNK`](command::CHUNK) frames sharing a
+//! message ID and reassembled by the receiver. Each chunk payload starts with
+//! a kind byte and the 16-byte message ID:
+//!
+//! - begin (`0`): followed by the command ID of the message, its total
+//!   payload length as a big-endian `u64`, and the first part of the payload
+//! - continue (`1`): followed by the next part of the payload
+//! - end (`2`): followed by the last part of the payload
+//!
+//! Reassembly buffers are bounded by a total size across all messages in
+//! progress, so a peer cannot make the receiver buffer without limit.
+
+use crate::{
+    command,
+    error::{Error, Result},
+};
+use rcpcore::Frame;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Default limit on the bytes buffered for messages being reassembled
+pub(crate) const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Chunk starting a message
+const BEGIN: u8 = 0;
+
+/// Chunk in the middle of a message
+const CONTINUE: u8 = 1;
+
+/// Chunk ending a message
+const END: u8 = 2;
+
+/// Size of the kind byte and message ID
+const HEADER_LEN: usize = 1 + 16;
+
+/// Size of the header of a begin chunk, with the command ID and total length
+const BEGIN_HEADER_LEN: usize = HEADER_LEN + 1 + 8;
+
+/// Split a frame into chunks whose payloads fit in `max_frame_size`
+///
+/// Frames that already fit are returned unchanged.
+pub(crate) fn split(frame: Frame, max_frame_size: usize) -> Result<Vec<Frame>> {
+    let payload = frame.payload();
+    if payload.len() <= max_frame_size {
+        return Ok(vec![frame]);
+    }
+    if max_frame_size <= BEGIN_HEADER_LEN {
+        return Err(Error::Protocol(format!(
+            "Maximum frame size of {} bytes is too small to split frames",
+            max_frame_size
+        )));
+    }
+
+    let id = Uuid::new_v4();
+    let chunk = |kind: u8, extra: &[u8], data: &[u8]| {
+        let mut chunk = Vec::with_capacity(HEADER_LEN + extra.len() + data.len());
+        chunk.push(kind);
+        chunk.extend_from_slice(id.as_bytes());
+        chunk.extend_from_slice(extra);
+        chunk.extend_from_slice(data);
+        Frame::new(command::CHUNK, chunk)
+    };
+
+    let mut begin = vec![frame.command_id()];
+    begin.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    let (first, mut rest) = payload.split_at(max_frame_size - BEGIN_HEADER_LEN);
+    let mut chunks = vec![chunk(BEGIN, &begin, first)];
+
+    let part_len = max_frame_size - HEADER_LEN;
+    while rest.len() > part_len {
+        let (part, tail) = rest.split_at(part_len);
+        chunks.push(chunk(CONTINUE, &[], part));
+        rest = tail;
+    }
+    chunks.push(chunk(END, &[], rest));
+
+    Ok(chunks)
+}
+
+/// Message being reassembled
+#[derive(Debug)]
+struct Partial {
+    /// Command ID of the message
+    command_id: u8,
+
+    /// Declared payload length
+    len: usize,
+
+    /// Payload received so far
+    data: Vec<u8>,
+}
+
+/// Reassembles messages from chunk frames
+#[derive(Debug)]
+pub(crate) struct Reassembler {
+    /// Limit on the declared lengths of all messages in progress
+    max_size: usize,
+
+    /// Declared lengths of all messages in progress
+    reserved: usize,
+
+    /// Messages in progress by message ID
+    messages: HashMap<Uuid, Partial>,
+}
+
+impl Reassembler {
+    /// Create a reassembler buffering at most `max_size` bytes
+    pub(crate) fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            reserved: 0,
+            messages: HashMap::new(),
+        }
+    }
+
+    /// Add a chunk frame, returning the message once it is complete
+    pub(crate) fn push(&mut self, frame: &Frame) -> Result<Option<Frame>> {
+        let payload = frame.payload();
+        if payload.len() < HEADER_LEN {
+            return Err(Error::Protocol("Truncated chunk header".to_string()));
+        }
+        let kind = payload[0];
+        let id = Uuid::from_slice(&payload[1..HEADER_LEN]).expect("16-byte message ID");
+        let mut data = &payload[HEADER_LEN..];
+
+        if kind == BEGIN {
+            if data.len() < BEGIN_HEADER_LEN - HEADER_LEN {
+                return Err(Error::Protocol("Truncated chunk header".to_string()));
+            }
+            let command_id = data[0];
+            let len = u64::from_be_bytes(data[1..9].try_into().expect("8-byte length"));
+            data = &data[9..];
+
+            self.remove(&id);
+            let len = usize::try_from(len)
+                .ok()
+                .filter(|len| self.reserved.saturating_add(*len) <= self.max_size)
+                .ok_or_else(|| {
+                    Error::Protocol(format!(
+                        "Chunked message of {} bytes exceeds the reassembly limit of {} bytes",
+                        len, self.max_size
+                    ))
+                })?;
+            self.reserved += len;
+            self.messages.insert(
+                id,
+                Partial {
+                    command_id,
+                    len,
+                    data: Vec::new(),
+                },
+            );
+        } else if kind != CONTINUE && kind != END {
+            return Err(Error::Protocol(format!("Unknown chunk kind {}", kind)));
+        }
+
+        let Some(partial) = self.messages.get_mut(&id) else {
+            return Err(Error::Protocol(format!("Chunk for unknown message {}", id)));
+        };
+        if partial.data.len() + data.len() > partial.len {
+            self.remove(&id);
+            return Err(Error::Protocol(format!(
+                "Chunked message {} exceeds its declared length",
+                id
+            )));
+        }
+        partial.data.extend_from_slice(data);
+
+        if kind != END {
+            return Ok(None);
+        }
+        let partial = self.remove(&id).expect("message in progress");
+        if partial.data.len() != partial.len {
+            return Err(Error::Protocol(format!(
+                "Chunked message {} ended after {} of {} bytes",
+                id,
+                partial.data.len(),
+                partial.len
+            )));
+        }
+        Ok(Some(Frame::new(partial.command_id, partial.data)))
+    }
+
+    /// Drop all messages in progress
+    pub(crate) fn clear(&mut self) {
+        self.messages.clear();
+        self.reserved = 0;
+    }
+
+    /// Stop reassembling a message
+    fn remove(&mut self, id: &Uuid) -> Option<Partial> {
+        let partial = self.messages.remove(id)?;
+        self.reserved -= partial.len;
+        Some(partial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_reassemble() {
+        let payload: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let frame = Frame::new(command::CLIPBOARD_DATA, payload.clone());
+
+        let chunks = split(frame.clone(), 100).unwrap();
+        assert!(chunks.len() > 2);
+        assert!(chunks.iter().all(|chunk| chunk.payload().len() <= 100));
+        assert!(chunks.iter().all(|c| c.command_id() == command::CHUNK));
+
+        let mut reassembler = Reassembler::new(DEFAULT_MAX_MESSAGE_SIZE);
+        let (last, rest) = chunks.split_last().unwrap();
+        for chunk in rest {
+            assert!(reassembler.push(chunk).unwrap().is_none());
+        }
+        let message = reassembler.push(last).unwrap().unwrap();
+        assert_eq!(message.command_id(), command::CLIPBOARD_DATA);
+        assert_eq!(message.payload(), payload);
+        assert_eq!(reassembler.reserved, 0);
+
+        // Frames that fit are left alone
+        assert_eq!(split(frame, 1000).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_reassembly_limit() {
+        let first = split(Frame::new(0x10, vec![1; 600]), 100).unwrap();
+        let second = split(Frame::new(0x10, vec![2; 600]), 100).unwrap();
+
+        // Messages in progress count towards the limit together
+        let mut reassembler = Reassembler::new(1000);
+        assert!(reassembler.push(&first[0]).unwrap().is_none());
+        let err = reassembler.push(&second[0]).unwrap_err();
+        assert!(err.to_string().contains("reassembly limit"), "{}", err);
+
+        // The rejected message is dropped, the other one completes
+        assert!(reassembler.push(&second[1]).is_err());
+        let message = first[1..]
+            .iter()
+            .map(|chunk| reassembler.push(chunk).unwrap())
+            .last()
+            .unwrap();
+        assert_eq!(message.unwrap().payload(), [1; 600]);
+    }
+}
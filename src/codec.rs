@@ -0,0 +1,252 @@
+//! Cancel safe framing of RCP connections
+//!
+//! rcpcore's `Protocol::read_frame` reads a frame's header and payload with
+//! separate `read_exact` calls into local buffers. It is not cancel safe:
+//! dropping its future part way through, for instance when another branch of
+//! `tokio::select!` completes first, loses the bytes read so far and leaves
+//! the stream in the middle of a frame, so every later frame is misread.
+//!
+//! The client itself never cancels a read. Its reader task awaits each frame
+//! to completion and is only aborted once the connection is abandoned, so a
+//! half-read frame is dropped together with the connection. Timeouts during
+//! the handshake likewise fail the whole connection.
+//!
+//! Code driving a connection by hand and racing reads against shutdown
+//! signals or idle timers should use [`FrameCodec`] with
+//! `tokio_util::codec::Framed` instead. Partially received frames stay in the
+//! `Framed` buffer, so `StreamExt::next` is cancel safe and can be used as a
+//! `select!` branch freely:
+//!
+//! ```no_run
+//! use futures_util::StreamExt;
+//! use rcpcli::codec::FrameCodec;
+//! use std::time::Duration;
+//! use tokio::net::TcpStream;
+//! use tokio_util::codec::Framed;
+//!
+//! # async fn example() -> rcpcli::Result<()> {
+//! let stream = TcpStream::connect(("127.0.0.1", rcpcli::DEFAULT_PORT)).await?;
+//! let mut framed = Framed::new(stream, FrameCodec::new());
+//! loop {
+//!     tokio::select! {
+//!         frame = framed.next() => match frame {
+//!             Some(frame) => println!("Received command {:02x}", frame?.command_id()),
+//!             None => break,
+//!         },
+//!         _ = tokio::time::sleep(Duration::from_secs(30)) => break,
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The codec reads and writes the same wire format as `Protocol`: the command
+//! ID, the payload length as a big-endian `u32`, then the payload. Frames
+//! written by one can be read by the other, so a connection may switch
+//! between them as long as no frame is left half read.
+
+use crate::error::{Error, Result};
+use rcpcore::Frame;
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio_util::bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Size of a frame header: the command ID and the payload length
+const HEADER_LEN: usize = 1 + 4;
+
+/// Codec for RCP frames, for use with `tokio_util::codec::Framed`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameCodec {
+    /// Largest payload accepted, if limited
+    max_frame_size: Option<usize>,
+}
+
+impl FrameCodec {
+    /// Create a codec accepting payloads of any size
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reject inbound frames with a payload larger than `max` bytes
+    ///
+    /// The length is checked as soon as the header has arrived, so an
+    /// oversized frame fails without its payload being buffered.
+    pub fn max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = Some(max);
+        self
+    }
+}
+
+impl Decoder for FrameCodec {
+    type Item = Frame;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Frame>> {
+        let Some(header) = src.get(..HEADER_LEN) else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes(header[1..].try_into().expect("4-byte length")) as usize;
+        if let Some(max) = self.max_frame_size.filter(|max| len > *max) {
+            return Err(Error::Protocol(format!(
+                "Frame of {} bytes exceeds the maximum of {} bytes",
+                len, max
+            )));
+        }
+
+        if src.len() < HEADER_LEN + len {
+            src.reserve(HEADER_LEN + len - src.len());
+            return Ok(None);
+        }
+        let command_id = src[0];
+        src.advance(HEADER_LEN);
+        let payload = src.split_to(len).to_vec();
+        Ok(Some(Frame::new(command_id, payload)))
+    }
+}
+
+impl Encoder<&Frame> for FrameCodec {
+    type Error = Error;
+
+    fn encode(&mut self, frame: &Frame, dst: &mut BytesMut) -> Result<()> {
+        let payload = frame.payload();
+        let len = u32::try_from(payload.len()).map_err(|_| {
+            Error::Protocol(format!(
+                "Frame of {} bytes is too large to encode",
+                payload.len()
+            ))
+        })?;
+        dst.reserve(HEADER_LEN + payload.len());
+        dst.put_u8(frame.command_id());
+        dst.put_u32(len);
+        dst.put_slice(payload);
+        Ok(())
+    }
+}
+
+impl Encoder<Frame> for FrameCodec {
+    type Error = Error;
+
+    fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> Result<()> {
+        self.encode(&frame, dst)
+    }
+}
+
+/// Reader failing as soon as a frame header announces a payload over a
+/// limit
+///
+/// `Protocol::read_frame` allocates the payload as announced by the header
+/// before reading it, so checking the frame it returns comes too late to
+/// guard against a huge length. This follows the frames in the bytes read
+/// through it and fails the read that completes an oversized header, before
+/// `Protocol` gets to allocate anything.
+#[derive(Debug)]
+pub(crate) struct FrameLimit<R> {
+    /// Underlying reader
+    inner: R,
+
+    /// Largest payload accepted, if limited
+    max_frame_size: Option<usize>,
+
+    /// Header of the next frame, as far as it has been read
+    header: [u8; HEADER_LEN],
+
+    /// Bytes of `header` read so far
+    header_read: usize,
+
+    /// Payload bytes of the current frame still to be read
+    payload_left: usize,
+}
+
+impl<R> FrameLimit<R> {
+    /// Follow the frames read from `inner`, rejecting payloads over
+    /// `max_frame_size` bytes
+    pub(crate) fn new(inner: R, max_frame_size: Option<usize>) -> Self {
+        Self {
+            inner,
+            max_frame_size,
+            header: [0; HEADER_LEN],
+            header_read: 0,
+            payload_left: 0,
+        }
+    }
+
+    /// Advance over bytes read, failing on an oversized frame header
+    fn advance(&mut self, mut bytes: &[u8]) -> io::Result<()> {
+        while !bytes.is_empty() {
+            if self.payload_left > 0 {
+                let skipped = self.payload_left.min(bytes.len());
+                self.payload_left -= skipped;
+                bytes = &bytes[skipped..];
+                continue;
+            }
+
+            let taken = (HEADER_LEN - self.header_read).min(bytes.len());
+            self.header[self.header_read..self.header_read + taken]
+                .copy_from_slice(&bytes[..taken]);
+            self.header_read += taken;
+            bytes = &bytes[taken..];
+            if self.header_read < HEADER_LEN {
+                break;
+            }
+
+            self.header_read = 0;
+            let len = u32::from_be_bytes(self.header[1..].try_into().expect("4-byte length"));
+            let len = len as usize;
+            if let Some(max) = self.max_frame_size.filter(|max| len > *max) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Frame of {} bytes exceeds the maximum of {} bytes",
+                        len, max
+                    ),
+                ));
+            }
+            self.payload_left = len;
+        }
+        Ok(())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for FrameLimit<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        std::task::ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        Poll::Ready(self.advance(&buf.filled()[filled..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_frame_limit() {
+        // A frame within the limit, then a header announcing 4 GiB
+        let mut bytes = vec![0x04, 0, 0, 0, 3, 1, 2, 3];
+        bytes.extend_from_slice(&[0x20, 0xFF, 0xFF, 0xFF, 0xFF]);
+        let mut reader = FrameLimit::new(&bytes[..], Some(16));
+
+        let mut frame = [0; 8];
+        reader.read_exact(&mut frame).await.unwrap();
+        let mut header = [0; HEADER_LEN];
+        let err = reader.read_exact(&mut header).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("exceeds the maximum"), "{}", err);
+
+        // Without a limit every frame passes
+        let mut reader = FrameLimit::new(&bytes[..], None);
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).await.unwrap();
+        assert_eq!(all, bytes);
+    }
+}
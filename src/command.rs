@@ -0,0 +1,237 @@
+//! Command identifiers used by the client that rcpcore does not define
+//!
+//! These occupy the `0xE0..=0xEF` range, which is reserved for client
+//! protocol extensions, and continue above it: the range has been full
+//! since [`ROOM_STATUS`], so [`FLOW_CREDIT`] takes `0xF0`, which rcpcore
+//! does not assign either, instead of reusing an ID that deployed servers
+//! already interpret. Servers that do not understand a command reply with
+//! an `Error` frame or ignore it.
+//!
+//! [`parse_command`] maps the command ID of a frame, from either set, to a
+//! [`ParsedCommand`].
+
+use rcpcore::{CommandId, Frame};
+
+/// Clipboard contents, carrying a [`ClipboardData`](crate::ClipboardData) payload
+pub const CLIPBOARD_DATA: u8 = 0xE0;
+
+/// Server-initiated re-key: carries an `AuthChallenge` from the server and an
+/// `AuthResponse` computed with the next pre-shared key from the client
+pub const REKEY: u8 = 0xE1;
+
+/// Cancel an in-flight request; the payload is the 16-byte message ID of the
+/// request followed by its command ID
+pub const CANCEL: u8 = 0xE2;
+
+/// Unsubscribe from a service; the payload is the service name, as in the
+/// subscription request
+pub const UNSUBSCRIBE: u8 = 0xE3;
+
+/// Part of a message split across frames because it exceeds the maximum
+/// frame size; see the `chunked` module for the payload layout
+pub const CHUNK: u8 = 0xE4;
+
+/// Session parameters pushed by the server mid-session, carrying a
+/// [`SessionConfigUpdate`](crate::SessionConfigUpdate) payload
+pub const CONFIG_UPDATE: u8 = 0xE5;
+
+/// Attach a data channel to an authenticated session; the payload is the
+/// 16-byte session ID, and the server answers with an `Ack` or an `Error`
+pub const DATA_CHANNEL: u8 = 0xE6;
+
+/// Ask for the applications running in the session; the payload is a
+/// 16-byte request ID, echoed in the [`APP_LIST`] reply
+pub const LIST_APPS: u8 = 0xE7;
+
+/// Applications running in the session, answering [`LIST_APPS`] with an
+/// [`AppList`](crate::AppList) payload
+pub const APP_LIST: u8 = 0xE8;
+
+/// Terminate an application in the session; the payload is a 16-byte
+/// request ID, the process ID as a big-endian `u32` and a force flag byte
+pub const TERMINATE_APP: u8 = 0xE9;
+
+/// Outcome of a [`TERMINATE_APP`] request, carrying an
+/// [`AppTerminated`](crate::AppTerminated) payload
+pub const APP_TERMINATED: u8 = 0xEA;
+
+/// Audio stream data, in the format last announced with [`AUDIO_FORMAT`]
+pub const AUDIO_STREAM: u8 = 0xEB;
+
+/// Format of the audio stream, such as its codec, sample rate and channels
+pub const AUDIO_FORMAT: u8 = 0xEC;
+
+/// Keyboard or pointer input, carrying an
+/// [`InputEvent`](crate::input::InputEvent) payload
+pub const INPUT_EVENT: u8 = 0xED;
+
+/// Join or leave a room of a collaborative server, carrying a
+/// [`RoomRequest`](crate::room::RoomRequest) payload
+pub const ROOM: u8 = 0xEE;
+
+/// Outcome of a [`ROOM`] request, carrying a
+/// [`RoomStatus`](crate::room::RoomStatus) payload
+pub const ROOM_STATUS: u8 = 0xEF;
+
+/// Credit for more inbound frames of a service, carrying a
+/// [`FlowCredit`](crate::flow::FlowCredit) payload
+pub const FLOW_CREDIT: u8 = 0xF0;
+
+/// Command of a frame, covering both the rcpcore commands and the client
+/// extensions above
+///
+/// Matching on a parsed command avoids comparing raw command IDs against
+/// `CommandId::X as u8` by hand:
+///
+/// ```
+/// use rcpcli::command::{parse_command, ParsedCommand};
+/// use rcpcore::{CommandId, Frame};
+///
+/// let frame = Frame::new(CommandId::Heartbeat as u8, Vec::new());
+/// assert_eq!(parse_command(&frame), Some(ParsedCommand::Heartbeat));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParsedCommand {
+    /// Authentication handshake
+    Auth,
+    /// Keep-alive heartbeat
+    Heartbeat,
+    /// Error report
+    Error,
+    /// Acknowledgement
+    Ack,
+    /// Application launch
+    LaunchApp,
+    /// Display stream frame
+    StreamFrame,
+    /// Display layout
+    DisplayInfo,
+    /// Display service subscription
+    SubscribeDisplay,
+    /// Input service subscription
+    SubscribeInput,
+    /// Audio service subscription
+    SubscribeAudio,
+    /// Clipboard service subscription
+    SubscribeClipboard,
+    /// File transfer service subscription
+    SubscribeFileTransfer,
+    /// Named service subscription
+    ServiceSubscribe,
+    /// Clipboard contents, see [`CLIPBOARD_DATA`]
+    ClipboardData,
+    /// Server-initiated re-key, see [`REKEY`]
+    Rekey,
+    /// Request cancellation, see [`CANCEL`]
+    Cancel,
+    /// Service unsubscription, see [`UNSUBSCRIBE`]
+    Unsubscribe,
+    /// Part of a multi-frame message, see [`CHUNK`]
+    Chunk,
+    /// Server session parameters, see [`CONFIG_UPDATE`]
+    ConfigUpdate,
+    /// Data channel attachment, see [`DATA_CHANNEL`]
+    DataChannel,
+    /// Running application query, see [`LIST_APPS`]
+    ListApps,
+    /// Running applications, see [`APP_LIST`]
+    AppList,
+    /// Application termination, see [`TERMINATE_APP`]
+    TerminateApp,
+    /// Application termination outcome, see [`APP_TERMINATED`]
+    AppTerminated,
+    /// Audio stream data, see [`AUDIO_STREAM`]
+    AudioStream,
+    /// Audio stream format, see [`AUDIO_FORMAT`]
+    AudioFormat,
+    /// Keyboard or pointer input, see [`INPUT_EVENT`]
+    InputEvent,
+    /// Room join or leave, see [`ROOM`]
+    Room,
+    /// Room request outcome, see [`ROOM_STATUS`]
+    RoomStatus,
+    /// Inbound frame credit, see [`FLOW_CREDIT`]
+    FlowCredit,
+}
+
+impl ParsedCommand {
+    /// Every command with its ID
+    const ALL: [(Self, u8); 30] = [
+        (Self::Auth, CommandId::Auth as u8),
+        (Self::Heartbeat, CommandId::Heartbeat as u8),
+        (Self::Error, CommandId::Error as u8),
+        (Self::Ack, CommandId::Ack as u8),
+        (Self::LaunchApp, CommandId::LaunchApp as u8),
+        (Self::StreamFrame, CommandId::StreamFrame as u8),
+        (Self::DisplayInfo, CommandId::DisplayInfo as u8),
+        (Self::SubscribeDisplay, CommandId::SubscribeDisplay as u8),
+        (Self::SubscribeInput, CommandId::SubscribeInput as u8),
+        (Self::SubscribeAudio, CommandId::SubscribeAudio as u8),
+        (
+            Self::SubscribeClipboard,
+            CommandId::SubscribeClipboard as u8,
+        ),
+        (
+            Self::SubscribeFileTransfer,
+            CommandId::SubscribeFileTransfer as u8,
+        ),
+        (Self::ServiceSubscribe, CommandId::ServiceSubscribe as u8),
+        (Self::ClipboardData, CLIPBOARD_DATA),
+        (Self::Rekey, REKEY),
+        (Self::Cancel, CANCEL),
+        (Self::Unsubscribe, UNSUBSCRIBE),
+        (Self::Chunk, CHUNK),
+        (Self::ConfigUpdate, CONFIG_UPDATE),
+        (Self::DataChannel, DATA_CHANNEL),
+        (Self::ListApps, LIST_APPS),
+        (Self::AppList, APP_LIST),
+        (Self::TerminateApp, TERMINATE_APP),
+        (Self::AppTerminated, APP_TERMINATED),
+        (Self::AudioStream, AUDIO_STREAM),
+        (Self::AudioFormat, AUDIO_FORMAT),
+        (Self::InputEvent, INPUT_EVENT),
+        (Self::Room, ROOM),
+        (Self::RoomStatus, ROOM_STATUS),
+        (Self::FlowCredit, FLOW_CREDIT),
+    ];
+
+    /// Get the command with the given ID, if it is known
+    pub fn from_id(command_id: u8) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find_map(|(command, id)| (id == command_id).then_some(command))
+    }
+
+    /// Whether the command controls the session rather than carrying
+    /// service data
+    pub fn is_control(self) -> bool {
+        !matches!(
+            self,
+            Self::LaunchApp
+                | Self::StreamFrame
+                | Self::DisplayInfo
+                | Self::ClipboardData
+                | Self::Chunk
+                | Self::ListApps
+                | Self::AppList
+                | Self::TerminateApp
+                | Self::AppTerminated
+                | Self::AudioStream
+                | Self::AudioFormat
+                | Self::InputEvent
+        )
+    }
+
+    /// Get the ID of the command
+    pub fn id(self) -> u8 {
+        Self::ALL
+            .into_iter()
+            .find_map(|(command, id)| (command == self).then_some(id))
+            .expect("every command has an ID")
+    }
+}
+
+/// Get the command of a frame, or None if its command ID is unknown
+pub fn parse_command(frame: &Frame) -> Option<ParsedCommand> {
+    ParsedCommand::from_id(frame.command_id())
+}
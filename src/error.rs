@@ -27,6 +27,10 @@ pub enum Error {
     #[error("Protocol error: {0}")]
     Protocol(String),
 
+    /// Invalid port number, such as 0 or one out of range
+    #[error("Invalid port: {0}")]
+    InvalidPort(String),
+
     /// Service error
     #[error("Service error: {0}")]
     Service(String),
@@ -35,6 +39,14 @@ pub enum Error {
     #[error("Session error: {0}")]
     Session(String),
 
+    /// The room to join has no space for another participant
+    #[error("Room full: {0}")]
+    RoomFull(String),
+
+    /// No room with the path exists on the server
+    #[error("Room not found: {0}")]
+    RoomNotFound(String),
+
     /// Timeout error
     #[error("Operation timed out: {0}")]
     Timeout(String),
@@ -60,6 +72,24 @@ pub enum Error {
     Other(String),
 }
 
+impl Error {
+    /// Check whether retrying the failed operation could succeed
+    ///
+    /// Network failures and timeouts are retryable; authentication and
+    /// protocol errors are not, since retrying would fail the same way.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::IO(_)
+                | Self::Connection(_)
+                | Self::Timeout(_)
+                | Self::Core(_)
+                | Self::WebSocket(_)
+        )
+    }
+}
+
+#[cfg(feature = "client")]
 impl From<tokio_tungstenite::tungstenite::Error> for Error {
     fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
         Self::WebSocket(err.to_string())
@@ -0,0 +1,179 @@
+//! Notifications about the client's connection lifecycle
+
+use crate::{
+    error::Error, server_error::ServerError, service_type::ServiceType,
+    session_config::SessionConfigUpdate,
+};
+use rcpcore::ConnectionState;
+use std::fmt;
+
+/// Client state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientState {
+    /// Disconnected
+    Disconnected,
+
+    /// Connecting
+    Connecting,
+
+    /// Connected but not authenticated
+    Connected,
+
+    /// Authenticating
+    Authenticating,
+
+    /// Authenticated and ready
+    Ready,
+
+    /// Closing
+    Closing,
+}
+
+impl From<ConnectionState> for ClientState {
+    fn from(state: ConnectionState) -> Self {
+        match state {
+            ConnectionState::Connected => Self::Connected,
+            ConnectionState::Authenticating => Self::Authenticating,
+            ConnectionState::Authenticated => Self::Ready,
+            ConnectionState::Closing => Self::Closing,
+            ConnectionState::Closed => Self::Disconnected,
+        }
+    }
+}
+
+/// State of the reconnect circuit breaker, see
+/// [`CircuitBreakerConfig`](crate::CircuitBreakerConfig)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Reconnect attempts are made as usual
+    Closed,
+
+    /// Too many attempts failed; none are made until the cooldown is over
+    Open,
+
+    /// The cooldown is over and a single trial attempt is allowed
+    HalfOpen,
+}
+
+/// Why the client disconnected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisconnectReason {
+    /// The application called `disconnect()`
+    Requested,
+
+    /// The server closed the connection
+    ServerClosed,
+
+    /// The connection failed with an I/O or protocol error
+    IoError(String),
+
+    /// The server stopped sending frames
+    HeartbeatTimeout,
+
+    /// Writing a frame to the server took longer than the write timeout
+    WriteTimeout,
+
+    /// The server rejected authentication; retrying would not help
+    AuthenticationFailed(String),
+
+    /// Reconnecting failed for another reason retrying would not fix, such
+    /// as an invalid configuration
+    Failed(String),
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Requested => write!(f, "disconnect requested"),
+            Self::ServerClosed => write!(f, "connection closed by server"),
+            Self::IoError(msg) => write!(f, "connection error: {}", msg),
+            Self::HeartbeatTimeout => write!(f, "server stopped responding"),
+            Self::WriteTimeout => write!(f, "server stopped accepting data"),
+            Self::AuthenticationFailed(msg) => write!(f, "authentication failed: {}", msg),
+            Self::Failed(msg) => write!(f, "failed: {}", msg),
+        }
+    }
+}
+
+impl From<&Error> for DisconnectReason {
+    /// Reason for giving up on a session after `error`
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::Auth(_) | Error::Authentication(_) => {
+                Self::AuthenticationFailed(error.to_string())
+            }
+            Error::IO(_)
+            | Error::Connection(_)
+            | Error::Timeout(_)
+            | Error::Core(_)
+            | Error::WebSocket(_)
+            | Error::Protocol(_)
+            | Error::Serialize(_)
+            | Error::Deserialize(_) => Self::IoError(error.to_string()),
+            Error::InvalidPort(_)
+            | Error::Service(_)
+            | Error::Session(_)
+            | Error::RoomFull(_)
+            | Error::RoomNotFound(_)
+            | Error::Other(_) => Self::Failed(error.to_string()),
+        }
+    }
+}
+
+/// Event emitted by the client
+///
+/// Every notification about the client goes through this one type, so a
+/// single `match` covers everything happening to it. See
+/// [`Client::subscribe_events`](crate::Client::subscribe_events) and
+/// [`Client::take_events`](crate::Client::take_events).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// The client moved to a new state
+    StateChanged(ClientState),
+
+    /// A session was established, including after automatic reconnects;
+    /// see [`Client::session_info`](crate::Client::session_info) for its
+    /// details
+    Connected,
+
+    /// An established session ended. Followed by
+    /// [`Reconnecting`](Self::Reconnecting) if the client reconnects, or by
+    /// [`Closed`](Self::Closed) if it stops for good.
+    Disconnected(DisconnectReason),
+
+    /// A reconnection attempt is about to start
+    Reconnecting {
+        /// Attempt number, starting at 1
+        attempt: u32,
+    },
+
+    /// The client reconnected and is ready again
+    Reconnected,
+
+    /// The client stopped for good and will not reconnect
+    Closed(DisconnectReason),
+
+    /// The server pushed new session parameters, which have been applied as
+    /// far as [`SessionConfigUpdate`] allows
+    ConfigUpdated(SessionConfigUpdate),
+
+    /// The server reported an error. Whether the session survives it is up
+    /// to the server; the client keeps running until told otherwise.
+    ServerError(ServerError),
+
+    /// The handler of a service stopped, because the service was
+    /// unsubscribed or the connection closed
+    ServiceClosed(ServiceType),
+
+    /// The client joined a room, including the configured room after each
+    /// (re)connect
+    RoomJoined {
+        /// Path of the room
+        path: String,
+        /// Number of participants in the room, including the client
+        participants: u32,
+    },
+
+    /// The reconnect circuit breaker changed state
+    CircuitBreaker(BreakerState),
+}
@@ -0,0 +1,104 @@
+//! Flow control of inbound service frames
+//!
+//! A server streaming display or audio data can produce frames faster than a
+//! slow client handles them, and without a limit the frames pile up in the
+//! socket and service channels. A service with a window, set through
+//! [`ServiceConfig::window_size`](crate::ServiceConfig::window_size), grants
+//! the server that many frames when it subscribes with a
+//! [`FLOW_CREDIT`](command::FLOW_CREDIT) frame. Servers that support flow
+//! control send no more frames than they were granted, and the client grants
+//! more as its handler consumes them.
+
+use crate::{
+    command,
+    error::{Error, Result},
+};
+use rcpcore::Frame;
+use serde::{Deserialize, Serialize};
+
+/// Credit for more inbound frames of a service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlowCredit {
+    /// Name of the service, as in the subscription request
+    pub service: String,
+
+    /// Number of further frames the server may send
+    pub credits: u32,
+}
+
+impl FlowCredit {
+    /// Encode the credit into a flow credit frame
+    pub fn to_frame(&self) -> Result<Frame> {
+        let payload = rcpcore::utils::to_bytes(self)?;
+        Ok(Frame::new(command::FLOW_CREDIT, payload))
+    }
+
+    /// Decode the credit from a flow credit frame
+    pub fn from_frame(frame: &Frame) -> Result<Self> {
+        if frame.command_id() != command::FLOW_CREDIT {
+            return Err(Error::Protocol(format!(
+                "Expected flow credit frame, got command {:02x}",
+                frame.command_id()
+            )));
+        }
+        Ok(rcpcore::utils::from_bytes(frame.payload())?)
+    }
+}
+
+/// Count of inbound frames consumed since credit was last granted
+#[derive(Debug)]
+pub(crate) struct CreditWindow {
+    /// Frames granted at once
+    size: u32,
+
+    /// Frames consumed and not yet granted again
+    consumed: u32,
+}
+
+impl CreditWindow {
+    /// Create a window of `size` frames
+    pub(crate) fn new(size: u32) -> Self {
+        Self {
+            size: size.max(1),
+            consumed: 0,
+        }
+    }
+
+    /// Credits granted when subscribing: the whole window
+    pub(crate) fn initial(&self) -> u32 {
+        self.size
+    }
+
+    /// Record a consumed frame, returning the credits to grant once half the
+    /// window is consumed
+    ///
+    /// Granting in halves rather than per frame keeps the credit traffic low
+    /// while the server always has credit left as long as the client keeps up.
+    pub(crate) fn consume(&mut self) -> Option<u32> {
+        self.consumed += 1;
+        if self.consumed < self.size.div_ceil(2) {
+            return None;
+        }
+        Some(std::mem::take(&mut self.consumed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credit_window() {
+        let mut window = CreditWindow::new(5);
+        assert_eq!(window.initial(), 5);
+        assert_eq!(window.consume(), None);
+        assert_eq!(window.consume(), None);
+        assert_eq!(window.consume(), Some(3));
+        assert_eq!(window.consume(), None);
+
+        // A window of one grants every frame
+        let mut window = CreditWindow::new(1);
+        assert_eq!(window.consume(), Some(1));
+        assert_eq!(window.consume(), Some(1));
+    }
+}
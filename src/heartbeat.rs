@@ -0,0 +1,98 @@
+//! Timestamped heartbeats
+//!
+//! Heartbeats may carry timestamps so each side can measure the round-trip
+//! time and estimate the clock skew between them without extra traffic. The
+//! payload starts with a version byte; version 1 continues with three
+//! big-endian `u64` fields:
+//!
+//! - the sender's monotonic timestamp in microseconds, meaningful only to
+//!   the sender
+//! - the sender's wall clock in microseconds since the Unix epoch
+//! - the latest monotonic timestamp received from the peer, echoed back, or
+//!   0 if there is none yet
+//!
+//! Later versions may append fields, so readers ignore trailing bytes. Empty
+//! heartbeats, as sent by older peers, carry no timing information and are
+//! still valid.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Current heartbeat payload version
+pub const HEARTBEAT_VERSION: u8 = 1;
+
+/// Size of a version 1 payload
+const PAYLOAD_LEN: usize = 1 + 3 * 8;
+
+/// Timing information carried by a heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatPayload {
+    /// Sender's monotonic timestamp in microseconds
+    pub timestamp_micros: u64,
+
+    /// Sender's wall clock in microseconds since the Unix epoch
+    pub wall_clock_micros: u64,
+
+    /// Latest timestamp received from the peer, echoed back
+    pub echo_micros: Option<u64>,
+}
+
+impl HeartbeatPayload {
+    /// Encode the payload
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(PAYLOAD_LEN);
+        payload.push(HEARTBEAT_VERSION);
+        payload.extend_from_slice(&self.timestamp_micros.to_be_bytes());
+        payload.extend_from_slice(&self.wall_clock_micros.to_be_bytes());
+        payload.extend_from_slice(&self.echo_micros.unwrap_or(0).to_be_bytes());
+        payload
+    }
+
+    /// Decode a heartbeat payload, returning None if it carries no timing
+    /// information the client understands
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        if payload
+            .first()
+            .is_none_or(|version| *version < HEARTBEAT_VERSION)
+            || payload.len() < PAYLOAD_LEN
+        {
+            return None;
+        }
+        let field = |index: usize| {
+            let start = 1 + index * 8;
+            u64::from_be_bytes(payload[start..start + 8].try_into().expect("8-byte field"))
+        };
+        Some(Self {
+            timestamp_micros: field(0),
+            wall_clock_micros: field(1),
+            echo_micros: Some(field(2)).filter(|echo| *echo != 0),
+        })
+    }
+}
+
+/// Current wall clock in microseconds since the Unix epoch
+pub fn wall_clock_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_micros() as u64)
+}
+
+/// Estimate how far the peer's wall clock is ahead of the local one, in
+/// microseconds
+///
+/// `rtt` is the round-trip time of the heartbeat carrying `peer_wall_micros`,
+/// which is assumed to have been taken halfway through it. Wall clocks jump
+/// when they are corrected and may be unset, so the estimate saturates
+/// rather than overflowing, and there is none if either clock reads 0, as
+/// [`wall_clock_micros`] does before the Unix epoch.
+pub fn clock_skew_micros(
+    peer_wall_micros: u64,
+    local_wall_micros: u64,
+    rtt: Duration,
+) -> Option<i64> {
+    if peer_wall_micros == 0 || local_wall_micros == 0 {
+        return None;
+    }
+    let midpoint = i128::from(local_wall_micros) - (rtt.as_micros() / 2) as i128;
+    let skew = i128::from(peer_wall_micros) - midpoint;
+    Some(skew.clamp(i64::MIN.into(), i64::MAX.into()) as i64)
+}
@@ -0,0 +1,423 @@
+//! Forwarding local input to the Input service
+//!
+//! An [`InputSource`] produces [`InputEvent`]s, for instance from a channel
+//! fed by a UI toolkit or, with the `evdev` feature on Linux, straight from
+//! an input device. An [`InputForwarder`] sends them on an Input service
+//! client, merging pointer motion that arrives faster than a set rate.
+//!
+//! Key codes are Linux evdev codes (`KEY_*` in `linux/input-event-codes.h`).
+//! Sources on other platforms translate their native codes to these, so the
+//! server only has to understand one mapping.
+
+use crate::{
+    command,
+    error::{Error, Result},
+    service::ServiceClient,
+};
+use futures_util::FutureExt;
+use rcpcore::Frame;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{self, Instant};
+
+#[cfg(all(feature = "evdev", target_os = "linux"))]
+pub use evdev::EvdevSource;
+
+/// Pointer button
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PointerButton {
+    /// Primary button
+    Left,
+
+    /// Secondary button
+    Right,
+
+    /// Wheel button
+    Middle,
+
+    /// Any other button, by evdev code (`BTN_*`)
+    Other(u16),
+}
+
+impl PointerButton {
+    /// Get the button with the given evdev code
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            0x110 => Self::Left,
+            0x111 => Self::Right,
+            0x112 => Self::Middle,
+            code => Self::Other(code),
+        }
+    }
+}
+
+/// Keyboard or pointer input event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputEvent {
+    /// Key pressed, or repeated while held, by evdev key code
+    KeyDown(u16),
+
+    /// Key released, by evdev key code
+    KeyUp(u16),
+
+    /// Pointer moved by a relative amount
+    PointerMotion {
+        /// Horizontal movement, positive to the right
+        dx: i32,
+        /// Vertical movement, positive downwards
+        dy: i32,
+    },
+
+    /// Pointer button pressed
+    ButtonDown(PointerButton),
+
+    /// Pointer button released
+    ButtonUp(PointerButton),
+
+    /// Wheel scrolled, in detents
+    Scroll {
+        /// Horizontal scroll, positive to the right
+        dx: i32,
+        /// Vertical scroll, positive upwards
+        dy: i32,
+    },
+}
+
+impl InputEvent {
+    /// Encode the event into an input frame
+    pub fn to_frame(&self) -> Result<Frame> {
+        let payload = rcpcore::utils::to_bytes(self)?;
+        Ok(Frame::new(command::INPUT_EVENT, payload))
+    }
+
+    /// Decode the event from an input frame
+    pub fn from_frame(frame: &Frame) -> Result<Self> {
+        if frame.command_id() != command::INPUT_EVENT {
+            return Err(Error::Protocol(format!(
+                "Expected input frame, got command {:02x}",
+                frame.command_id()
+            )));
+        }
+        Ok(rcpcore::utils::from_bytes(frame.payload())?)
+    }
+}
+
+/// Producer of input events, such as an input device
+#[async_trait::async_trait]
+pub trait InputSource: Send {
+    /// Wait for the next event
+    ///
+    /// Returns `None` once the source is exhausted. Must be cancel safe:
+    /// [`InputForwarder`] drops the future to send merged pointer motion
+    /// on time, and no event may be lost when it does.
+    async fn next_event(&mut self) -> Result<Option<InputEvent>>;
+}
+
+#[async_trait::async_trait]
+impl InputSource for mpsc::Receiver<InputEvent> {
+    async fn next_event(&mut self) -> Result<Option<InputEvent>> {
+        Ok(self.recv().await)
+    }
+}
+
+#[async_trait::async_trait]
+impl InputSource for mpsc::UnboundedReceiver<InputEvent> {
+    async fn next_event(&mut self) -> Result<Option<InputEvent>> {
+        Ok(self.recv().await)
+    }
+}
+
+/// Forwarder of input events from a source to the Input service
+///
+/// ```no_run
+/// use rcpcli::{Client, InputEvent, InputForwarder, ServiceType};
+/// use tokio::sync::mpsc;
+///
+/// # async fn example(client: Client) -> rcpcli::Result<()> {
+/// let input = client.subscribe_service(ServiceType::Input).await?;
+/// let (tx, mut rx) = mpsc::unbounded_channel();
+/// tokio::spawn(async move {
+///     InputForwarder::new(input)
+///         .max_motion_rate(120)
+///         .run(&mut rx)
+///         .await
+/// });
+///
+/// // Feed events from the UI toolkit
+/// tx.send(InputEvent::PointerMotion { dx: 4, dy: -2 }).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct InputForwarder {
+    /// Client of the Input service
+    service: ServiceClient,
+
+    /// Minimum time between pointer motion events, if limited
+    motion_interval: Option<Duration>,
+}
+
+impl InputForwarder {
+    /// Create a forwarder sending on an Input service client
+    pub fn new(service: ServiceClient) -> Self {
+        Self {
+            service,
+            motion_interval: None,
+        }
+    }
+
+    /// Limit how many pointer motion events are sent per second
+    ///
+    /// Motion arriving faster is added up and sent as one event once the
+    /// interval has passed. Other events are never delayed; motion still
+    /// waiting is sent ahead of them to keep the order. 0 removes the limit.
+    pub fn max_motion_rate(mut self, per_sec: u32) -> Self {
+        self.motion_interval = (per_sec > 0).then(|| Duration::from_secs(1) / per_sec);
+        self
+    }
+
+    /// Forward events until the source is exhausted
+    ///
+    /// Whenever the source has no event ready, and before returning, the
+    /// events sent so far are [flushed](ServiceClient::flush), so a burst
+    /// of input reaches the server as soon as it ends. Returns the number of
+    /// events sent, which is lower than the number read when motion was
+    /// merged. Fails if the source fails or the service stops.
+    pub async fn run<S: InputSource + ?Sized>(&self, source: &mut S) -> Result<u64> {
+        let mut sent = 0;
+        let mut flushed = 0;
+        let mut pending = None;
+        let mut next_motion = Instant::now();
+        loop {
+            let event = match source.next_event().now_or_never() {
+                Some(event) => event?,
+                None => {
+                    if sent > flushed {
+                        self.service.flush().await?;
+                        flushed = sent;
+                    }
+                    match pending {
+                        Some(_) => tokio::select! {
+                            event = source.next_event() => event?,
+                            _ = time::sleep_until(next_motion) => {
+                                self.send_pending(&mut pending, &mut sent).await?;
+                                next_motion = self.next_motion_after(Instant::now());
+                                continue;
+                            }
+                        },
+                        None => source.next_event().await?,
+                    }
+                }
+            };
+            let Some(event) = event else {
+                break;
+            };
+
+            match event {
+                InputEvent::PointerMotion { dx, dy } if self.motion_interval.is_some() => {
+                    let (total_dx, total_dy) = pending.get_or_insert((0, 0));
+                    *total_dx = total_dx.saturating_add(dx);
+                    *total_dy = total_dy.saturating_add(dy);
+
+                    let now = Instant::now();
+                    if now >= next_motion {
+                        self.send_pending(&mut pending, &mut sent).await?;
+                        next_motion = self.next_motion_after(now);
+                    }
+                }
+                event => {
+                    self.send_pending(&mut pending, &mut sent).await?;
+                    self.service.send_input(&event).await?;
+                    sent += 1;
+                }
+            }
+        }
+
+        self.send_pending(&mut pending, &mut sent).await?;
+        if sent > flushed {
+            self.service.flush().await?;
+        }
+        Ok(sent)
+    }
+
+    /// Send the motion added up so far, if any
+    async fn send_pending(&self, pending: &mut Option<(i32, i32)>, sent: &mut u64) -> Result<()> {
+        if let Some((dx, dy)) = pending.take() {
+            self.service
+                .send_input(&InputEvent::PointerMotion { dx, dy })
+                .await?;
+            *sent += 1;
+        }
+        Ok(())
+    }
+
+    /// Earliest time the next motion event may be sent
+    fn next_motion_after(&self, now: Instant) -> Instant {
+        now + self.motion_interval.unwrap_or_default()
+    }
+}
+
+#[cfg(all(feature = "evdev", target_os = "linux"))]
+mod evdev {
+    use super::{InputEvent, InputSource, PointerButton};
+    use crate::error::Result;
+    use std::os::raw::c_long;
+    use std::path::Path;
+    use tokio::fs::File;
+    use tokio::io::AsyncReadExt;
+
+    /// Size of a `struct input_event`: a `struct timeval`, then the type
+    /// and code as `u16` and the value as `i32`
+    const EVENT_SIZE: usize = 2 * std::mem::size_of::<c_long>() + 8;
+
+    const EV_KEY: u16 = 0x01;
+    const EV_REL: u16 = 0x02;
+    const REL_X: u16 = 0x00;
+    const REL_Y: u16 = 0x01;
+    const REL_HWHEEL: u16 = 0x06;
+    const REL_WHEEL: u16 = 0x08;
+
+    /// Codes of the mouse buttons, `BTN_LEFT` to `BTN_TASK`
+    const MOUSE_BUTTONS: std::ops::RangeInclusive<u16> = 0x110..=0x117;
+
+    /// Input source reading a Linux evdev device
+    ///
+    /// Keys, mouse buttons, relative motion and wheels are forwarded; other
+    /// events, such as absolute axes and synchronization markers, are
+    /// skipped. The device is not grabbed, so local applications keep
+    /// receiving its input as well.
+    #[derive(Debug)]
+    pub struct EvdevSource {
+        /// Device file
+        file: File,
+
+        /// Event being read, kept across cancelled reads
+        buffer: [u8; EVENT_SIZE],
+
+        /// Bytes of the buffer read so far
+        filled: usize,
+    }
+
+    impl EvdevSource {
+        /// Open an evdev device, such as `/dev/input/event0`
+        ///
+        /// Reading input devices usually requires membership of the `input`
+        /// group.
+        pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+            Ok(Self {
+                file: File::open(path).await?,
+                buffer: [0; EVENT_SIZE],
+                filled: 0,
+            })
+        }
+
+        /// Map a raw event to an input event, if it is one that is forwarded
+        fn decode(event: &[u8; EVENT_SIZE]) -> Option<InputEvent> {
+            let field = |offset: usize| [event[offset], event[offset + 1]];
+            let kind = u16::from_ne_bytes(field(EVENT_SIZE - 8));
+            let code = u16::from_ne_bytes(field(EVENT_SIZE - 6));
+            let value = i32::from_ne_bytes(event[EVENT_SIZE - 4..].try_into().ok()?);
+
+            match (kind, code) {
+                (EV_KEY, code) if MOUSE_BUTTONS.contains(&code) => {
+                    let button = PointerButton::from_code(code);
+                    Some(match value {
+                        0 => InputEvent::ButtonUp(button),
+                        _ => InputEvent::ButtonDown(button),
+                    })
+                }
+                (EV_KEY, code) => Some(match value {
+                    0 => InputEvent::KeyUp(code),
+                    // A value of 2 is an auto-repeat of a held key
+                    _ => InputEvent::KeyDown(code),
+                }),
+                (EV_REL, REL_X) => Some(InputEvent::PointerMotion { dx: value, dy: 0 }),
+                (EV_REL, REL_Y) => Some(InputEvent::PointerMotion { dx: 0, dy: value }),
+                (EV_REL, REL_HWHEEL) => Some(InputEvent::Scroll { dx: value, dy: 0 }),
+                (EV_REL, REL_WHEEL) => Some(InputEvent::Scroll { dx: 0, dy: value }),
+                _ => None,
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl InputSource for EvdevSource {
+        async fn next_event(&mut self) -> Result<Option<InputEvent>> {
+            loop {
+                while self.filled < EVENT_SIZE {
+                    let read = self.file.read(&mut self.buffer[self.filled..]).await?;
+                    if read == 0 {
+                        return Ok(None);
+                    }
+                    self.filled += read;
+                }
+                self.filled = 0;
+
+                if let Some(event) = Self::decode(&self.buffer) {
+                    return Ok(Some(event));
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Raw `input_event` record with a zero timestamp
+        fn event(kind: u16, code: u16, value: i32) -> [u8; EVENT_SIZE] {
+            let mut event = [0; EVENT_SIZE];
+            event[EVENT_SIZE - 8..EVENT_SIZE - 6].copy_from_slice(&kind.to_ne_bytes());
+            event[EVENT_SIZE - 6..EVENT_SIZE - 4].copy_from_slice(&code.to_ne_bytes());
+            event[EVENT_SIZE - 4..].copy_from_slice(&value.to_ne_bytes());
+            event
+        }
+
+        #[test]
+        fn test_decode() {
+            const KEY_A: u16 = 30;
+            const BTN_RIGHT: u16 = 0x111;
+            const EV_SYN: u16 = 0x00;
+            const EV_ABS: u16 = 0x03;
+
+            let decode = |kind, code, value| EvdevSource::decode(&event(kind, code, value));
+
+            // Press, auto-repeat and release of a key
+            assert_eq!(decode(EV_KEY, KEY_A, 1), Some(InputEvent::KeyDown(KEY_A)));
+            assert_eq!(decode(EV_KEY, KEY_A, 2), Some(InputEvent::KeyDown(KEY_A)));
+            assert_eq!(decode(EV_KEY, KEY_A, 0), Some(InputEvent::KeyUp(KEY_A)));
+
+            let right = PointerButton::from_code(BTN_RIGHT);
+            assert_eq!(
+                decode(EV_KEY, BTN_RIGHT, 1),
+                Some(InputEvent::ButtonDown(right))
+            );
+            assert_eq!(
+                decode(EV_KEY, BTN_RIGHT, 0),
+                Some(InputEvent::ButtonUp(right))
+            );
+
+            assert_eq!(
+                decode(EV_REL, REL_X, -4),
+                Some(InputEvent::PointerMotion { dx: -4, dy: 0 })
+            );
+            assert_eq!(
+                decode(EV_REL, REL_Y, 3),
+                Some(InputEvent::PointerMotion { dx: 0, dy: 3 })
+            );
+            assert_eq!(
+                decode(EV_REL, REL_WHEEL, -1),
+                Some(InputEvent::Scroll { dx: 0, dy: -1 })
+            );
+            assert_eq!(
+                decode(EV_REL, REL_HWHEEL, 1),
+                Some(InputEvent::Scroll { dx: 1, dy: 0 })
+            );
+
+            // Synchronization markers and absolute axes are skipped
+            assert_eq!(decode(EV_SYN, 0, 0), None);
+            assert_eq!(decode(EV_ABS, 0, 100), None);
+        }
+    }
+}
@@ -3,16 +3,90 @@
 //! This library provides a client implementation for the Rust/Remote Control Protocol (RCP).
 //! It allows applications to connect to RCP servers and use their services like display
 //! streaming, input control, clipboard sharing, and file transfers.
+//!
+//! # Features
+//!
+//! - `client` (default): the async client, its services and the `rcpcli`
+//!   binary. Without it only the plain data types remain, such as
+//!   [`ConnectionString`], [`ServiceType`] and the error type, for tools that
+//!   just parse or validate RCP URLs. This drops the client's own
+//!   dependencies, but not tokio: `rcpcore`, which the remaining types build
+//!   on, still depends on it.
+//! - `evdev`: `input::EvdevSource`, which reads input events from a Linux
+//!   evdev device such as `/dev/input/event0`. Only available on Linux.
+//! - `tracing`: runs each service handler in a `tracing` span named
+//!   `service_handler`, with `service_type` and `subscription_id` fields.
+//!   The handlers log through the `log` crate, so their records land in the
+//!   span once bridged into `tracing`, as `tracing_subscriber`'s `init()`
+//!   does.
 
+#[cfg(feature = "client")]
+pub mod auth;
+#[cfg(feature = "client")]
+pub mod breaker;
+#[cfg(feature = "client")]
+pub mod checksum;
+#[cfg(feature = "client")]
+mod chunked;
+#[cfg(feature = "client")]
 pub mod client;
+#[cfg(feature = "client")]
+pub mod codec;
+pub mod command;
 pub mod connection_string;
 pub mod error;
+pub mod event;
+#[cfg(feature = "client")]
+pub mod flow;
+pub mod heartbeat;
+#[cfg(feature = "client")]
+pub mod input;
+#[cfg(feature = "client")]
+pub mod room;
+pub mod server_error;
+#[cfg(feature = "client")]
 pub mod service;
+pub mod service_type;
+pub mod session_config;
+#[cfg(feature = "client")]
+mod split;
+#[cfg(feature = "client")]
+pub mod testing;
+#[cfg(feature = "client")]
+pub mod throttle;
+pub mod transport;
+#[cfg(feature = "client")]
+pub mod writer;
 
-pub use client::{Client, ClientBuilder, ClientConfig, ClientState};
+#[cfg(feature = "client")]
+pub use auth::{AuthContext, AuthHandler, AuthHandlers};
+#[cfg(feature = "client")]
+pub use breaker::CircuitBreakerConfig;
+#[cfg(feature = "client")]
+pub use client::{
+    Client, ClientBuilder, ClientConfig, ConnectCallback, ConnectionStats, Credentials,
+    DisconnectCallback, FrameTap, ProtocolConfig,
+};
 pub use connection_string::ConnectionString;
 pub use error::{Error, Result};
-pub use service::{builtin, Service, ServiceClient, ServiceFactory, ServiceMessage, ServiceType};
+pub use event::{BreakerState, ClientEvent, ClientState, DisconnectReason};
+#[cfg(feature = "client")]
+pub use flow::FlowCredit;
+#[cfg(feature = "client")]
+pub use input::{InputEvent, InputForwarder, InputSource, PointerButton};
+#[cfg(feature = "client")]
+pub use room::{RoomAction, RoomOutcome, RoomRequest, RoomStatus};
+pub use server_error::ServerError;
+#[cfg(feature = "client")]
+pub use service::{
+    builtin, AppInfo, AppList, AppTerminated, ClipboardData, RequestHandle, RequestLimits, Service,
+    ServiceClient, ServiceConfig, ServiceFactory, ServiceMessage, ServiceStats, TerminateOutcome,
+};
+pub use service_type::{FramePriority, ServiceType};
+pub use session_config::SessionConfigUpdate;
+#[cfg(feature = "client")]
+pub use throttle::ThrottleStats;
+pub use transport::Transport;
 
 /// Default port for RCP connections
 pub const DEFAULT_PORT: u16 = rcpcore::DEFAULT_PORT;
@@ -26,17 +100,48 @@ pub const DEFAULT_KEEP_ALIVE_SECS: u64 = 30;
 /// Default reconnection delay in milliseconds
 pub const DEFAULT_RECONNECT_DELAY_MS: u64 = 2000;
 
-/// A simple example of using the RCP client:
+/// Default socket read buffer size in bytes
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Default number of missed keep-alive intervals before the connection is considered dead
+pub const DEFAULT_HEARTBEAT_MISS_COUNT: u32 = 3;
+
+/// Default number of messages a service channel can hold
+pub const DEFAULT_SERVICE_CHANNEL_CAPACITY: usize = 100;
+
+/// Default maximum number of services subscribed at once
+pub const DEFAULT_MAX_SERVICES: usize = 32;
+
+/// Install a `tracing` subscriber printing to stdout, as the `rcpcli`
+/// binary does
+///
+/// Events up to `level` are printed. Records of the `log` crate, which the
+/// client logs through, are bridged into `tracing` as well. Fails rather than
+/// panicking if a global subscriber or logger is already installed, so
+/// callers embedding the client can ignore the error and keep their own.
+#[cfg(feature = "client")]
+pub fn init_tracing(level: tracing::Level) -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .try_init()
+        .map_err(|e| Error::Other(format!("Failed to set tracing subscriber: {}", e)))
+}
+
+/// A simple example of using the RCP client, against an in-process
+/// [`LoopbackServer`](testing::LoopbackServer) standing in for a real server:
+///
+/// ```rust
+/// use rcpcli::{testing::LoopbackServer, Client, ServiceType};
 ///
-/// ```rust,no_run
-/// use rcpcli::{Client, ServiceType};
-/// use tokio::time::{sleep, Duration};
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// // Start a local server; a real one would be at e.g. 192.168.1.100:8000
+/// let server = LoopbackServer::start().await?;
 ///
-/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
 /// // Create the client
 /// let client = Client::builder()
-///     .host("192.168.1.100")
-///     .port(8000)
+///     .host("127.0.0.1")
+///     .port(server.port())
 ///     .client_name("My RCP Client")
 ///     .auth_psk("my_secret_key")
 ///     .build();
@@ -53,8 +158,9 @@ pub const DEFAULT_RECONNECT_DELAY_MS: u64 = 2000;
 /// // Subscribe to the input service
 /// let input_service = client.subscribe_service(ServiceType::Input).await?;
 ///
-/// // Keep the client running
-/// sleep(Duration::from_secs(60)).await;
+/// // Measure the round trip to the server
+/// let rtt = client.ping().await?;
+/// println!("Round trip: {:?}", rtt);
 ///
 /// // Disconnect when done
 /// client.disconnect().await?;
@@ -1,8 +1,7 @@
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
-use rcpcli::Client;
+use rcpcli::{Client, ConnectionString};
 use rcpcore::AuthMethod;
-use tracing_subscriber::FmtSubscriber;
 use uuid::Uuid;
 
 /// RCP Client - Command line interface for Rust/Remote Control Protocol
@@ -57,6 +56,43 @@ enum Commands {
     },
 }
 
+impl Cli {
+    /// Resolve the connection to use, taking anything the connection string
+    /// leaves out from the command line arguments
+    fn connection(
+        &self,
+        connection_string: Option<&str>,
+        default_psk: Option<&str>,
+    ) -> Result<ConnectionString> {
+        let defaults = ConnectionString {
+            username: Some(self.client_name.clone()),
+            password: default_psk.map(str::to_string),
+            host: self.host.clone(),
+            port: Some(self.port),
+            path: None,
+            params: Default::default(),
+        };
+
+        let conn = match connection_string {
+            Some(conn_str) => {
+                tracing::info!("Connecting using connection string: {}", conn_str);
+                ConnectionString::parse(conn_str)
+                    .context("Failed to parse connection string")?
+                    .with_defaults(&defaults)
+            }
+            None => defaults,
+        };
+
+        tracing::info!(
+            "Connecting to server at {}:{}",
+            conn.host,
+            conn.port.unwrap_or(rcpcli::DEFAULT_PORT)
+        );
+
+        Ok(conn)
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Parse command line arguments
@@ -70,8 +106,7 @@ async fn main() -> Result<()> {
     };
 
     // Initialize the logging subscriber
-    let subscriber = FmtSubscriber::builder().with_max_level(log_level).finish();
-    tracing::subscriber::set_global_default(subscriber).expect("Failed to set tracing subscriber");
+    rcpcli::init_tracing(log_level)?;
 
     // Process command
     match &cli.command {
@@ -79,42 +114,22 @@ async fn main() -> Result<()> {
             connection_string,
             psk,
         }) => {
-            // Create client builder based on connection string or command line arguments
-            let mut builder = Client::builder();
-
-            if let Some(conn_str) = connection_string {
-                // Use connection string
-                builder = builder
-                    .connection_string(conn_str)
-                    .context("Failed to parse connection string")?;
-
-                // Log connection details from the parsed connection string
-                tracing::info!("Connecting using connection string: {}", conn_str);
-            } else {
-                // Use command line arguments
-                builder = builder
-                    .host(cli.host.clone())
-                    .port(cli.port)
-                    .client_name(cli.client_name.clone());
-
-                tracing::info!("Connecting to server at {}:{}", cli.host, cli.port);
+            // Fill in whatever the connection string leaves out from the
+            // command line arguments, defaulting the PSK to "test_key". An
+            // explicit --psk wins over a password in the connection string.
+            let mut conn = cli.connection(connection_string.as_deref(), Some("test_key"))?;
+            if let Some(auth_psk) = psk {
+                conn.password = Some(auth_psk.clone());
             }
 
-            // Set authentication method and PSK if provided
-            builder = builder
+            // CLI sessions are one-shot unless the connection string asks to reconnect
+            let builder = Client::builder()
+                .auto_reconnect(false)
+                .connection(&conn)
+                .context("Invalid connection string")?
                 .client_id(Uuid::new_v4())
                 .auth_method(AuthMethod::PreSharedKey);
 
-            // Use PSK from command line argument or default to "test_key" from config
-            if let Some(auth_psk) = psk {
-                builder = builder.auth_psk(auth_psk);
-            } else if let Some(_conn_str) = connection_string {
-                // PSK might already be set from connection string - nothing to do
-            } else {
-                // Default to "test_key" when no PSK provided
-                builder = builder.auth_psk("test_key");
-            }
-
             // Build the client
             let client = builder.build();
 
@@ -142,29 +157,13 @@ async fn main() -> Result<()> {
             command,
             args,
         }) => {
-            // Create client builder based on connection string or command line arguments
-            let mut builder = Client::builder();
-
-            if let Some(conn_str) = connection_string {
-                // Use connection string
-                builder = builder
-                    .connection_string(conn_str)
-                    .context("Failed to parse connection string")?;
-
-                // Log connection details from the parsed connection string
-                tracing::info!("Connecting using connection string: {}", conn_str);
-            } else {
-                // Use command line arguments
-                builder = builder
-                    .host(cli.host.clone())
-                    .port(cli.port)
-                    .client_name(cli.client_name.clone());
-
-                tracing::info!("Connecting to server at {}:{}", cli.host, cli.port);
-            }
+            let conn = cli.connection(connection_string.as_deref(), None)?;
 
-            // Set authentication method
-            builder = builder
+            // CLI sessions are one-shot unless the connection string asks to reconnect
+            let builder = Client::builder()
+                .auto_reconnect(false)
+                .connection(&conn)
+                .context("Invalid connection string")?
                 .client_id(Uuid::new_v4())
                 .auth_method(AuthMethod::PreSharedKey);
 
@@ -0,0 +1,107 @@
+//! Rooms of collaborative servers
+//!
+//! Collaborative servers group sessions into rooms named by a path, such as
+//! the `/room/42` of `rcp://host/room/42`. The client joins and leaves rooms
+//! with [`ROOM`](command::ROOM) frames, and the server answers each with a
+//! [`ROOM_STATUS`](command::ROOM_STATUS) frame carrying the outcome.
+
+use crate::{
+    command,
+    error::{Error, Result},
+};
+use rcpcore::Frame;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// What a room request asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoomAction {
+    /// Join the room
+    Join,
+
+    /// Leave the room
+    Leave,
+}
+
+/// Request to join or leave a room
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomRequest {
+    /// ID of the request, echoed in the reply
+    pub request_id: Uuid,
+
+    /// Join or leave
+    pub action: RoomAction,
+
+    /// Path of the room, such as `/room/42`
+    pub path: String,
+}
+
+impl RoomRequest {
+    /// Encode the request into a room frame
+    pub fn to_frame(&self) -> Result<Frame> {
+        let payload = rcpcore::utils::to_bytes(self)?;
+        Ok(Frame::new(command::ROOM, payload))
+    }
+
+    /// Decode the request from a room frame
+    pub fn from_frame(frame: &Frame) -> Result<Self> {
+        if frame.command_id() != command::ROOM {
+            return Err(Error::Protocol(format!(
+                "Expected room frame, got command {:02x}",
+                frame.command_id()
+            )));
+        }
+        Ok(rcpcore::utils::from_bytes(frame.payload())?)
+    }
+}
+
+/// Outcome of a room request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoomOutcome {
+    /// The room was joined
+    Joined {
+        /// Number of participants in the room, including the client
+        participants: u32,
+    },
+
+    /// The room was left
+    Left,
+
+    /// The room has no space for another participant
+    Full,
+
+    /// No room with the path exists
+    NotFound,
+
+    /// The request failed for another reason
+    Failed(String),
+}
+
+/// Outcome of a room request, as reported by the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomStatus {
+    /// ID of the request this answers
+    pub request_id: Uuid,
+
+    /// Outcome
+    pub outcome: RoomOutcome,
+}
+
+impl RoomStatus {
+    /// Encode the outcome into a room status frame
+    pub fn to_frame(&self) -> Result<Frame> {
+        let payload = rcpcore::utils::to_bytes(self)?;
+        Ok(Frame::new(command::ROOM_STATUS, payload))
+    }
+
+    /// Decode the outcome from a room status frame
+    pub fn from_frame(frame: &Frame) -> Result<Self> {
+        if frame.command_id() != command::ROOM_STATUS {
+            return Err(Error::Protocol(format!(
+                "Expected room status frame, got command {:02x}",
+                frame.command_id()
+            )));
+        }
+        Ok(rcpcore::utils::from_bytes(frame.payload())?)
+    }
+}
@@ -0,0 +1,117 @@
+//! Errors reported by the server
+//!
+//! The server reports errors with an `Error` frame. Older servers send the
+//! message as plain UTF-8 text. Structured errors start with a zero byte,
+//! which plain text never does, followed by:
+//!
+//! - the error code as a big-endian `u16`
+//! - the length of the service name as one byte, 0 if the error is not
+//!   specific to a service
+//! - the service name, in its [`Display`](std::fmt::Display) form
+//! - the message as UTF-8 text, filling the rest of the payload
+
+use crate::service_type::ServiceType;
+use rcpcore::{CommandId, Frame};
+use std::fmt;
+
+/// Marker byte that starts a structured error payload
+const STRUCTURED_MARKER: u8 = 0;
+
+/// Error reported by the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerError {
+    /// Server-defined error code, [`UNSPECIFIED`](Self::UNSPECIFIED) for
+    /// plain-text errors from older servers
+    pub code: u16,
+
+    /// Human-readable description
+    pub message: String,
+
+    /// Service the error relates to, if any
+    pub service: Option<ServiceType>,
+}
+
+impl ServerError {
+    /// Code of errors that carry no code, such as plain-text errors
+    pub const UNSPECIFIED: u16 = 0;
+
+    /// Create a server error
+    pub fn new(code: u16, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            service: None,
+        }
+    }
+
+    /// Attach the service the error relates to
+    pub fn with_service(mut self, service: ServiceType) -> Self {
+        self.service = Some(service);
+        self
+    }
+
+    /// Encode the error as a structured payload
+    pub fn to_payload(&self) -> Vec<u8> {
+        let service = self.service.map(|s| s.to_string()).unwrap_or_default();
+        let service = &service.as_bytes()[..service.len().min(u8::MAX as usize)];
+        let mut payload = Vec::with_capacity(4 + service.len() + self.message.len());
+        payload.push(STRUCTURED_MARKER);
+        payload.extend_from_slice(&self.code.to_be_bytes());
+        payload.push(service.len() as u8);
+        payload.extend_from_slice(service);
+        payload.extend_from_slice(self.message.as_bytes());
+        payload
+    }
+
+    /// Encode the error into an error frame
+    pub fn to_frame(&self) -> Frame {
+        Frame::new(CommandId::Error as u8, self.to_payload())
+    }
+
+    /// Decode an error payload
+    ///
+    /// Payloads that are not structured, or are truncated, are taken as a
+    /// plain-text message with an unspecified code. Unknown service names
+    /// are dropped rather than failing the whole error.
+    pub fn from_payload(payload: &[u8]) -> Self {
+        Self::parse_structured(payload).unwrap_or_else(|| Self {
+            code: Self::UNSPECIFIED,
+            message: String::from_utf8_lossy(payload).to_string(),
+            service: None,
+        })
+    }
+
+    fn parse_structured(payload: &[u8]) -> Option<Self> {
+        let (&marker, rest) = payload.split_first()?;
+        if marker != STRUCTURED_MARKER || rest.len() < 3 {
+            return None;
+        }
+        let code = u16::from_be_bytes([rest[0], rest[1]]);
+        let service_len = rest[2] as usize;
+        let rest = &rest[3..];
+        if rest.len() < service_len {
+            return None;
+        }
+        let (service, message) = rest.split_at(service_len);
+        let service = std::str::from_utf8(service)
+            .ok()
+            .and_then(|name| name.parse().ok());
+        Some(Self {
+            code,
+            message: String::from_utf8_lossy(message).to_string(),
+            service,
+        })
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.code != Self::UNSPECIFIED {
+            write!(f, "[{}] ", self.code)?;
+        }
+        if let Some(service) = self.service {
+            write!(f, "{}: ", service)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
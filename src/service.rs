@@ -1,88 +1,187 @@
-use crate::error::{Error, Result};
+use crate::{
+    command::{self, parse_command, ParsedCommand},
+    error::{Error, Result},
+    input::InputEvent,
+    server_error::ServerError,
+};
+use futures_util::future::BoxFuture;
 use log::{debug, trace};
 use rcpcore::{CommandId, Frame};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
-use std::str::FromStr;
-use tokio::sync::{mpsc, oneshot};
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::time::{self, Instant};
 use uuid::Uuid;
 
-/// Service type enumeration
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum ServiceType {
-    /// Display service for screen sharing
-    Display,
+pub use crate::service_type::{FramePriority, ServiceType};
 
-    /// Input service for sending keyboard/mouse events
-    Input,
+/// Clipboard contents tagged with their MIME type
+///
+/// Clipboards carry more than text, so the payload is kept as raw bytes and
+/// never passed through a lossy UTF-8 conversion.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClipboardData {
+    /// MIME type of the contents, e.g. `text/plain;charset=utf-8`
+    pub mime_type: String,
 
-    /// Audio service for streaming audio
-    Audio,
+    /// Raw contents
+    pub bytes: Vec<u8>,
+}
 
-    /// Clipboard service for clipboard synchronization
-    Clipboard,
+impl ClipboardData {
+    /// MIME type used for plain text contents
+    pub const TEXT_MIME_TYPE: &'static str = "text/plain;charset=utf-8";
 
-    /// File transfer service
-    FileTransfer,
+    /// MIME type used for PNG image contents
+    pub const PNG_MIME_TYPE: &'static str = "image/png";
 
-    /// Application launching service
-    App,
+    /// Create clipboard contents with an explicit MIME type
+    pub fn new(mime_type: impl Into<String>, bytes: Vec<u8>) -> Self {
+        Self {
+            mime_type: mime_type.into(),
+            bytes,
+        }
+    }
 
-    /// Custom service
-    Custom(u8),
-}
+    /// Create plain text clipboard contents
+    pub fn text(text: &str) -> Self {
+        Self::new(Self::TEXT_MIME_TYPE, text.as_bytes().to_vec())
+    }
 
-impl ServiceType {
-    /// Get the string representation of a service type
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            Self::Display => "display",
-            Self::Input => "input",
-            Self::Audio => "audio",
-            Self::Clipboard => "clipboard",
-            Self::FileTransfer => "file-transfer",
-            Self::App => "app",
-            Self::Custom(_) => "custom",
+    /// Create PNG image clipboard contents
+    pub fn image_png(bytes: Vec<u8>) -> Self {
+        Self::new(Self::PNG_MIME_TYPE, bytes)
+    }
+
+    /// Get the contents as text, if they are text and valid UTF-8
+    pub fn as_text(&self) -> Option<&str> {
+        if self.mime_type.starts_with("text/") {
+            std::str::from_utf8(&self.bytes).ok()
+        } else {
+            None
         }
     }
 
-    /// Get the command ID for subscribing to this service
-    pub fn subscription_command(&self) -> u8 {
-        match self {
-            Self::Display => CommandId::SubscribeDisplay as u8,
-            Self::Input => CommandId::SubscribeInput as u8,
-            Self::Audio => CommandId::SubscribeAudio as u8,
-            Self::Clipboard => CommandId::SubscribeClipboard as u8,
-            Self::FileTransfer => CommandId::SubscribeFileTransfer as u8,
-            Self::App => CommandId::ServiceSubscribe as u8, // Use generic service subscription for App
-            Self::Custom(id) => *id,
+    /// Encode the contents into a clipboard frame
+    pub fn to_frame(&self) -> Result<Frame> {
+        let payload = rcpcore::utils::to_bytes(self)?;
+        Ok(Frame::new(command::CLIPBOARD_DATA, payload))
+    }
+
+    /// Decode the contents from a clipboard frame
+    pub fn from_frame(frame: &Frame) -> Result<Self> {
+        if frame.command_id() != command::CLIPBOARD_DATA {
+            return Err(Error::Protocol(format!(
+                "Expected clipboard frame, got command {:02x}",
+                frame.command_id()
+            )));
         }
+        Ok(rcpcore::utils::from_bytes(frame.payload())?)
     }
 }
 
-impl FromStr for ServiceType {
-    type Err = ();
+/// Application running in the session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppInfo {
+    /// Process ID on the server
+    pub pid: u32,
+
+    /// Application name
+    pub name: String,
+
+    /// When the application was started
+    pub started_at: SystemTime,
+}
+
+/// Applications running in the session, as reported by the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppList {
+    /// ID of the request this answers
+    pub request_id: Uuid,
 
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "display" => Ok(Self::Display),
-            "input" => Ok(Self::Input),
-            "audio" => Ok(Self::Audio),
-            "clipboard" => Ok(Self::Clipboard),
-            "file-transfer" => Ok(Self::FileTransfer),
-            "app" => Ok(Self::App),
-            _ => Err(()),
+    /// Running applications
+    pub apps: Vec<AppInfo>,
+}
+
+impl AppList {
+    /// Encode the list into an app list frame
+    pub fn to_frame(&self) -> Result<Frame> {
+        let payload = rcpcore::utils::to_bytes(self)?;
+        Ok(Frame::new(command::APP_LIST, payload))
+    }
+
+    /// Decode the list from an app list frame
+    pub fn from_frame(frame: &Frame) -> Result<Self> {
+        if frame.command_id() != command::APP_LIST {
+            return Err(Error::Protocol(format!(
+                "Expected app list frame, got command {:02x}",
+                frame.command_id()
+            )));
         }
+        Ok(rcpcore::utils::from_bytes(frame.payload())?)
     }
 }
 
-impl fmt::Display for ServiceType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.as_str())
+/// Outcome of terminating an application
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TerminateOutcome {
+    /// The application was terminated
+    Terminated,
+
+    /// No application with the process ID is running
+    NotFound,
+
+    /// The application could not be terminated, with the reason
+    Failed(String),
+}
+
+/// Outcome of a terminate request, as reported by the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppTerminated {
+    /// ID of the request this answers
+    pub request_id: Uuid,
+
+    /// Process ID of the application
+    pub pid: u32,
+
+    /// Outcome
+    pub outcome: TerminateOutcome,
+}
+
+impl AppTerminated {
+    /// Encode the outcome into an app terminated frame
+    pub fn to_frame(&self) -> Result<Frame> {
+        let payload = rcpcore::utils::to_bytes(self)?;
+        Ok(Frame::new(command::APP_TERMINATED, payload))
+    }
+
+    /// Decode the outcome from an app terminated frame
+    pub fn from_frame(frame: &Frame) -> Result<Self> {
+        if frame.command_id() != command::APP_TERMINATED {
+            return Err(Error::Protocol(format!(
+                "Expected app terminated frame, got command {:02x}",
+                frame.command_id()
+            )));
+        }
+        Ok(rcpcore::utils::from_bytes(frame.payload())?)
     }
 }
 
 /// Service message with request-response channel
+///
+/// Create messages with [`new`](Self::new) or
+/// [`new_request`](Self::new_request) rather than struct literals, so fields
+/// can be added without breaking callers.
 #[derive(Debug)]
+#[non_exhaustive]
 pub struct ServiceMessage {
     /// Message ID
     pub id: Uuid,
@@ -90,8 +189,58 @@ pub struct ServiceMessage {
     /// Frame containing the message
     pub frame: Frame,
 
+    /// Frames written right after `frame`, with no other frames in between
+    pub batch: Vec<Frame>,
+
+    /// Scheduling priority of the frames, overriding the priority of the
+    /// service (None to use the service's priority)
+    ///
+    /// Protocol control frames such as cancellations always go first.
+    pub priority: Option<FramePriority>,
+
     /// Response channel
     pub response_tx: Option<oneshot::Sender<Result<Frame>>>,
+
+    /// Whether the frame came from the server, in which case it is only
+    /// passed to the handler and counts against the service's flow control
+    /// window
+    pub(crate) inbound: bool,
+}
+
+impl ServiceMessage {
+    /// Create a message that expects no response
+    pub fn new(frame: Frame) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            frame,
+            batch: Vec::new(),
+            priority: None,
+            response_tx: None,
+            inbound: false,
+        }
+    }
+
+    /// Create a request message, with the receiver of its response
+    pub fn new_request(frame: Frame) -> (Self, oneshot::Receiver<Result<Frame>>) {
+        let (tx, rx) = oneshot::channel();
+        let message = Self {
+            response_tx: Some(tx),
+            ..Self::new(frame)
+        };
+        (message, rx)
+    }
+
+    /// Write `batch` right after the frame, with no other frames in between
+    pub fn with_batch(mut self, batch: Vec<Frame>) -> Self {
+        self.batch = batch;
+        self
+    }
+
+    /// Schedule the frames with `priority` instead of the service's priority
+    pub fn with_priority(mut self, priority: FramePriority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
 }
 
 impl Clone for ServiceMessage {
@@ -99,7 +248,10 @@ impl Clone for ServiceMessage {
         Self {
             id: self.id,
             frame: self.frame.clone(),
+            batch: self.batch.clone(),
+            priority: self.priority,
             response_tx: None, // Can't clone the oneshot sender
+            inbound: self.inbound,
         }
     }
 }
@@ -107,6 +259,9 @@ impl Clone for ServiceMessage {
 /// Generic service trait
 #[async_trait::async_trait]
 pub trait Service: Send + Sync {
+    /// Get the type of this service
+    fn service_type(&self) -> ServiceType;
+
     /// Start the service
     async fn start(&mut self) -> Result<()>;
 
@@ -114,7 +269,114 @@ pub trait Service: Send + Sync {
     async fn stop(&mut self) -> Result<()>;
 
     /// Handle an incoming message
+    ///
+    /// For a message on its way to the server, an error keeps its frame
+    /// from being written, e.g. for a request the service refuses.
     async fn handle_message(&mut self, message: ServiceMessage) -> Result<()>;
+
+    /// Called once the server has acknowledged the subscription
+    ///
+    /// Frames sent through `client` here are written after the
+    /// acknowledgement, e.g. to request the initial state of the service.
+    async fn on_subscribed(&mut self, _client: &ServiceClient) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the service is unsubscribed while still connected
+    ///
+    /// Frames sent through `client` here are written before the server is
+    /// told about the unsubscription.
+    async fn on_unsubscribed(&mut self, _client: &ServiceClient) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Progress of frames through a service channel, used to implement flushing
+#[derive(Debug, Default)]
+pub(crate) struct WriteProgress {
+    /// Messages queued on the channel
+    queued: AtomicU64,
+
+    /// Messages fully processed by the service handler
+    processed: AtomicU64,
+
+    /// Notified whenever `processed` advances
+    notify: Notify,
+
+    /// Whether sends are refused while the queue drains
+    draining: AtomicBool,
+}
+
+impl WriteProgress {
+    /// Record that the handler finished with a message
+    pub(crate) fn mark_processed(&self) {
+        self.processed.fetch_add(1, Ordering::AcqRel);
+        self.notify.notify_waiters();
+    }
+}
+
+/// Inbound traffic of a service, see [`ServiceClient::stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServiceStats {
+    /// Frames received from the server for the service
+    pub frames_received: u64,
+
+    /// Payload bytes of the frames received
+    pub bytes_received: u64,
+
+    /// When the latest frame was received, None before the first
+    pub last_frame_at: Option<Instant>,
+}
+
+/// Inbound traffic counters, shared by every client of a subscription
+#[derive(Debug, Default)]
+struct InboundCounters {
+    /// Frames received
+    frames: AtomicU64,
+
+    /// Payload bytes received
+    bytes: AtomicU64,
+
+    /// When the latest frame was received
+    last_frame_at: std::sync::Mutex<Option<Instant>>,
+}
+
+impl InboundCounters {
+    /// Record a frame received from the server
+    fn record(&self, frame: &Frame) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.bytes
+            .fetch_add(frame.payload().len() as u64, Ordering::Relaxed);
+        *self.last_frame_at.lock().unwrap() = Some(Instant::now());
+    }
+}
+
+/// Interval at which a retried request checks for the service to return
+const RETRY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Finds the current client of a service, which changes when the client
+/// reconnects; None while the client is not ready
+#[derive(Clone)]
+pub(crate) struct ServiceLookup(
+    Arc<dyn Fn(ServiceType) -> BoxFuture<'static, Option<ServiceClient>> + Send + Sync>,
+);
+
+impl ServiceLookup {
+    /// Create a lookup from a function
+    pub(crate) fn new(
+        lookup: impl Fn(ServiceType) -> BoxFuture<'static, Option<ServiceClient>>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Self(Arc::new(lookup))
+    }
+}
+
+impl fmt::Debug for ServiceLookup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ServiceLookup")
+    }
 }
 
 /// Client-side service client
@@ -128,6 +390,15 @@ pub struct ServiceClient {
 
     /// Message sender channel
     tx: mpsc::Sender<ServiceMessage>,
+
+    /// Progress of queued messages through the handler
+    progress: Arc<WriteProgress>,
+
+    /// Traffic received for the service
+    inbound: Arc<InboundCounters>,
+
+    /// Lookup of the service's current client, for retrying requests
+    lookup: Option<ServiceLookup>,
 }
 
 impl ServiceClient {
@@ -141,6 +412,32 @@ impl ServiceClient {
             service_type,
             service_name,
             tx,
+            progress: Arc::new(WriteProgress::default()),
+            inbound: Arc::new(InboundCounters::default()),
+            lookup: None,
+        }
+    }
+
+    /// Let retried requests find the service's current client
+    pub(crate) fn with_lookup(mut self, lookup: ServiceLookup) -> Self {
+        self.lookup = Some(lookup);
+        self
+    }
+
+    /// Get the progress tracker shared with the service handler
+    pub(crate) fn write_progress(&self) -> Arc<WriteProgress> {
+        Arc::clone(&self.progress)
+    }
+
+    /// Create a handle that does not keep the service channel open
+    pub(crate) fn downgrade(&self) -> WeakServiceClient {
+        WeakServiceClient {
+            service_type: self.service_type,
+            service_name: self.service_name.clone(),
+            tx: self.tx.downgrade(),
+            progress: Arc::clone(&self.progress),
+            inbound: Arc::clone(&self.inbound),
+            lookup: self.lookup.clone(),
         }
     }
 
@@ -154,58 +451,499 @@ impl ServiceClient {
         &self.service_name
     }
 
+    /// Whether the service handler is still running
+    ///
+    /// Once the handler stops, for example because the service failed to
+    /// start or the client disconnected, every send fails. Check this to
+    /// detect a dead service before sending.
+    pub fn is_alive(&self) -> bool {
+        !self.tx.is_closed()
+    }
+
+    /// Get the number of messages the service channel can hold
+    pub fn channel_capacity(&self) -> usize {
+        self.tx.max_capacity()
+    }
+
+    /// Get the traffic received for the service
+    ///
+    /// Frames are counted as they are routed to the service, whether or not
+    /// the handler has got to them yet. Comparing two snapshots gives the
+    /// frame rate, and the time since the latest frame shows whether the
+    /// stream has stalled. The counts cover this subscription only; the
+    /// client a reconnect subscribes with starts from zero.
+    pub fn stats(&self) -> ServiceStats {
+        ServiceStats {
+            frames_received: self.inbound.frames.load(Ordering::Relaxed),
+            bytes_received: self.inbound.bytes.load(Ordering::Relaxed),
+            last_frame_at: *self.inbound.last_frame_at.lock().unwrap(),
+        }
+    }
+
     /// Send a message and get a response
     pub async fn send_request(&self, frame: Frame) -> Result<Frame> {
-        let (tx, rx) = oneshot::channel();
-        let msg = ServiceMessage {
-            id: Uuid::new_v4(),
-            frame,
-            response_tx: Some(tx),
-        };
+        self.start_request(frame).await?.await
+    }
 
-        // Send the message to the service handler
-        trace!("Sending request message to service {}", self.service_name);
-        self.tx.send(msg).await.map_err(|_| {
-            Error::Service(format!(
-                "Failed to send message to service {}",
-                self.service_name
-            ))
-        })?;
+    /// Send a message with an explicit scheduling priority and get a response
+    ///
+    /// Use [`FramePriority::High`] for latency-sensitive messages such as
+    /// input, and [`FramePriority::Low`] for bulk transfers.
+    pub async fn send_request_with_priority(
+        &self,
+        frame: Frame,
+        priority: FramePriority,
+    ) -> Result<Frame> {
+        self.queue_request(frame, Some(priority)).await?.await
+    }
 
-        // Wait for the response
-        trace!("Waiting for response from service {}", self.service_name);
-        let response = rx.await.map_err(|_| {
-            Error::Service(format!(
-                "Failed to receive response from service {}",
-                self.service_name
-            ))
-        })??;
+    /// Send an idempotent request, retrying it if the connection fails
+    ///
+    /// The request may reach the server more than once, so only use this for
+    /// commands that are safe to repeat; everything else should use
+    /// [`send_request`](Self::send_request). On a retryable error, or when
+    /// the service handler stopped because the connection dropped, the
+    /// request is sent again up to `retries` times. Before each retry the
+    /// client gets up to `backoff` to become ready again, after which the
+    /// request goes to the service's current client, e.g. one resubscribed
+    /// by [`Client::queue_subscription`](crate::Client::queue_subscription).
+    pub async fn send_request_with_retry(
+        &self,
+        frame: Frame,
+        retries: u32,
+        backoff: Duration,
+    ) -> Result<Frame> {
+        let mut service = self.clone();
+        let mut attempt = 0;
+        loop {
+            let error = match service.send_request(frame.clone()).await {
+                Ok(reply) => return Ok(reply),
+                Err(e) => e,
+            };
+            let retryable = error.is_retryable() || !service.is_alive();
+            if attempt >= retries || !retryable {
+                return Err(error);
+            }
 
-        Ok(response)
+            attempt += 1;
+            debug!(
+                "Retrying request to service {} ({}/{}): {}",
+                self.service_name, attempt, retries, error
+            );
+            service = self.next_live_client(service, backoff).await;
+        }
+    }
+
+    /// Wait `backoff` before retrying through `current`, or until a live
+    /// client of the service turns up if `current` has stopped
+    async fn next_live_client(&self, current: ServiceClient, backoff: Duration) -> ServiceClient {
+        let deadline = Instant::now() + backoff;
+        if current.is_alive() {
+            time::sleep_until(deadline).await;
+            return current;
+        }
+
+        loop {
+            if let Some(lookup) = &self.lookup {
+                if let Some(found) = (lookup.0)(self.service_type).await {
+                    if found.is_alive() {
+                        return found;
+                    }
+                }
+            }
+            if Instant::now() >= deadline {
+                return current;
+            }
+            time::sleep_until(deadline.min(Instant::now() + RETRY_POLL_INTERVAL)).await;
+        }
+    }
+
+    /// Send a message and return a handle to the pending response
+    ///
+    /// The handle can be awaited for the response, or cancelled with
+    /// [`RequestHandle::cancel`].
+    pub async fn start_request(&self, frame: Frame) -> Result<RequestHandle> {
+        self.queue_request(frame, None).await
+    }
+
+    /// Queue a request message, returning a handle to the pending response
+    async fn queue_request(
+        &self,
+        frame: Frame,
+        priority: Option<FramePriority>,
+    ) -> Result<RequestHandle> {
+        let command_id = frame.command_id();
+        let (mut msg, rx) = ServiceMessage::new_request(frame);
+        msg.priority = priority;
+        let id = msg.id;
+
+        // Send the message to the service handler
+        trace!("Sending request message to service {}", self.service_name);
+        self.enqueue(msg).await?;
+
+        Ok(RequestHandle {
+            id,
+            command_id,
+            rx,
+            service: self.clone(),
+        })
     }
 
     /// Send a message without expecting a response
     pub async fn send_fire_and_forget(&self, frame: Frame) -> Result<()> {
-        let msg = ServiceMessage {
-            id: Uuid::new_v4(),
-            frame,
-            response_tx: None,
+        self.send_message(frame, Vec::new()).await
+    }
+
+    /// Send frames that must reach the server consecutively
+    ///
+    /// The frames are queued to the writer as one unit, so no frame of this
+    /// or any other service is written between them. Returns once they are
+    /// queued, like [`send_fire_and_forget`](Self::send_fire_and_forget).
+    pub async fn send_atomic(&self, frames: Vec<Frame>) -> Result<()> {
+        let mut frames = frames.into_iter();
+        let Some(frame) = frames.next() else {
+            return Ok(());
         };
+        self.send_message(frame, frames.collect()).await
+    }
+
+    /// Queue a message without a response channel
+    async fn send_message(&self, frame: Frame, batch: Vec<Frame>) -> Result<()> {
+        let msg = ServiceMessage::new(frame).with_batch(batch);
 
         // Send the message to the service handler
         trace!(
             "Sending fire-and-forget message to service {}",
             self.service_name
         );
-        self.tx.send(msg).await.map_err(|_| {
-            Error::Service(format!(
-                "Failed to send message to service {}",
+        self.enqueue(msg).await
+    }
+
+    /// Queue a message to the service handler, unless the service is
+    /// draining
+    async fn enqueue(&self, msg: ServiceMessage) -> Result<()> {
+        if self.progress.draining.load(Ordering::Acquire) {
+            return Err(Error::Service(format!(
+                "Service {} is draining",
                 self.service_name
-            ))
+            )));
+        }
+        self.enqueue_internal(msg).await
+    }
+
+    /// Queue a message on behalf of the client itself, even while draining
+    async fn enqueue_internal(&self, msg: ServiceMessage) -> Result<()> {
+        self.tx.send(msg).await.map_err(|_| {
+            Error::Service(format!("Service {} is no longer active", self.service_name))
         })?;
+        self.progress.queued.fetch_add(1, Ordering::AcqRel);
 
         Ok(())
     }
+
+    /// Wait until every message queued so far has been handled and written
+    /// to the connection
+    pub async fn flush(&self) -> Result<()> {
+        let target = self.progress.queued.load(Ordering::Acquire);
+
+        loop {
+            let notified = self.progress.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.progress.processed.load(Ordering::Acquire) >= target {
+                return Ok(());
+            }
+
+            tokio::select! {
+                _ = notified => {}
+                _ = self.tx.closed() => {
+                    return Err(Error::Service(format!(
+                        "Service {} stopped before flushing",
+                        self.service_name
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Stop accepting sends and wait until every message queued so far has
+    /// been handled and written to the connection
+    ///
+    /// Every later send on this service fails, through any of its clients,
+    /// while inbound frames are still delivered to the handler. The service
+    /// stays subscribed until unsubscribed; subscribing again afterwards
+    /// gives a client that accepts sends.
+    pub async fn drain(&self) -> Result<()> {
+        self.progress.draining.store(true, Ordering::Release);
+        self.flush().await
+    }
+
+    /// Deliver an inbound frame to the handler, even while draining
+    pub(crate) async fn deliver(&self, frame: Frame) -> Result<()> {
+        self.inbound.record(&frame);
+        let msg = ServiceMessage {
+            inbound: true,
+            ..ServiceMessage::new(frame)
+        };
+        self.enqueue_internal(msg).await
+    }
+
+    /// Send a request on behalf of the client, such as the unsubscription,
+    /// even while draining
+    pub(crate) async fn send_internal_request(&self, frame: Frame) -> Result<Frame> {
+        let command_id = frame.command_id();
+        let (msg, rx) = ServiceMessage::new_request(frame);
+        let id = msg.id;
+        self.enqueue_internal(msg).await?;
+        RequestHandle {
+            id,
+            command_id,
+            rx,
+            service: self.clone(),
+        }
+        .response()
+        .await
+    }
+
+    /// Send clipboard contents, preserving their MIME type
+    pub async fn send_clipboard(&self, data: &ClipboardData) -> Result<()> {
+        self.send_fire_and_forget(data.to_frame()?).await
+    }
+
+    /// Send a keyboard or pointer input event
+    ///
+    /// Only available on the [`Input`](ServiceType::Input) service. To
+    /// forward events from a device, see
+    /// [`InputForwarder`](crate::input::InputForwarder).
+    pub async fn send_input(&self, event: &InputEvent) -> Result<()> {
+        if self.service_type != ServiceType::Input {
+            return Err(Error::Service(format!(
+                "Service {} cannot send input",
+                self.service_name
+            )));
+        }
+        self.send_fire_and_forget(event.to_frame()?).await
+    }
+
+    /// List the applications running in the session
+    ///
+    /// Only available on the [`App`](ServiceType::App) service. Waits for the
+    /// server's reply, so it does not return if the server never answers.
+    pub async fn list_apps(&self) -> Result<Vec<AppInfo>> {
+        let reply = self
+            .send_app_request(command::LIST_APPS, &[], "list applications")
+            .await?;
+        Ok(AppList::from_frame(&reply)?.apps)
+    }
+
+    /// Terminate an application in the session and wait for the server to
+    /// confirm
+    ///
+    /// `force` kills the application outright (like `SIGKILL`) rather than
+    /// asking it to exit (like `SIGTERM`). Only available on the
+    /// [`App`](ServiceType::App) service. Fails with [`Error::Service`] if no
+    /// application with the process ID is running.
+    pub async fn terminate_app(&self, pid: u32, force: bool) -> Result<()> {
+        let mut args = pid.to_be_bytes().to_vec();
+        args.push(force as u8);
+        let reply = self
+            .send_app_request(command::TERMINATE_APP, &args, "terminate application")
+            .await?;
+        match AppTerminated::from_frame(&reply)?.outcome {
+            TerminateOutcome::Terminated => Ok(()),
+            TerminateOutcome::NotFound => Err(Error::Service(format!(
+                "No application with process ID {} is running",
+                pid
+            ))),
+            TerminateOutcome::Failed(reason) => Err(Error::Service(format!(
+                "Failed to terminate application {}: {}",
+                pid, reason
+            ))),
+        }
+    }
+
+    /// Send an App service request with a fresh request ID followed by
+    /// `args`, and wait for the server's reply
+    async fn send_app_request(&self, command_id: u8, args: &[u8], action: &str) -> Result<Frame> {
+        if self.service_type != ServiceType::App {
+            return Err(Error::Service(format!(
+                "Service {} cannot {}",
+                self.service_name, action
+            )));
+        }
+
+        let mut payload = Uuid::new_v4().as_bytes().to_vec();
+        payload.extend_from_slice(args);
+        let reply = self.send_request(Frame::new(command_id, payload)).await?;
+        if parse_command(&reply) == Some(ParsedCommand::Error) {
+            return Err(Error::Service(format!(
+                "Failed to {}: {}",
+                action,
+                ServerError::from_payload(reply.payload())
+            )));
+        }
+        Ok(reply)
+    }
+
+    /// Send a frame built from a raw command ID and payload
+    ///
+    /// This is a low-level escape hatch for protocol development: the frame
+    /// is sent through the service channel as-is, without any typed wrapper
+    /// or validation of the command ID against the service. Prefer the typed
+    /// APIs where they exist.
+    pub async fn send_raw(&self, command_id: u8, payload: Vec<u8>) -> Result<()> {
+        debug!(
+            "Sending raw frame {:02x} ({} bytes) to service {}",
+            command_id,
+            payload.len(),
+            self.service_name
+        );
+        self.send_fire_and_forget(Frame::new(command_id, payload))
+            .await
+    }
+}
+
+/// Service client handle held by the service handler itself
+///
+/// The handler stops once every [`ServiceClient`] is dropped, so it must not
+/// hold a strong one.
+pub(crate) struct WeakServiceClient {
+    service_type: ServiceType,
+    service_name: String,
+    tx: mpsc::WeakSender<ServiceMessage>,
+    progress: Arc<WriteProgress>,
+    inbound: Arc<InboundCounters>,
+    lookup: Option<ServiceLookup>,
+}
+
+impl WeakServiceClient {
+    /// Get a service client if the service channel is still open
+    pub(crate) fn upgrade(&self) -> Option<ServiceClient> {
+        Some(ServiceClient {
+            service_type: self.service_type,
+            service_name: self.service_name.clone(),
+            tx: self.tx.upgrade()?,
+            progress: Arc::clone(&self.progress),
+            inbound: Arc::clone(&self.inbound),
+            lookup: self.lookup.clone(),
+        })
+    }
+}
+
+/// Handle to an in-flight request started with [`ServiceClient::start_request`]
+///
+/// Await the handle to get the response.
+#[derive(Debug)]
+pub struct RequestHandle {
+    /// Message ID of the request
+    id: Uuid,
+
+    /// Command ID of the request
+    command_id: u8,
+
+    /// Response channel
+    rx: oneshot::Receiver<Result<Frame>>,
+
+    /// Service the request was sent through
+    service: ServiceClient,
+}
+
+impl RequestHandle {
+    /// Get the message ID of the request
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Wait for the response
+    pub async fn response(self) -> Result<Frame> {
+        let service_name = self.service.service_name;
+        trace!("Waiting for response from service {}", service_name);
+        self.rx.await.map_err(|_| {
+            Error::Service(format!(
+                "Failed to receive response from service {}",
+                service_name
+            ))
+        })?
+    }
+
+    /// Cancel the request
+    ///
+    /// The pending response is dropped, and a request still queued on the
+    /// client is discarded without reaching the server. Either way a cancel
+    /// frame carrying the request's message ID and command ID is sent, since
+    /// the client cannot tell whether the request was already written; the
+    /// server abandons the work, or ignores the cancel if it never saw the
+    /// request.
+    pub async fn cancel(self) -> Result<()> {
+        drop(self.rx);
+
+        let mut payload = self.id.as_bytes().to_vec();
+        payload.push(self.command_id);
+        self.service.send_raw(command::CANCEL, payload).await
+    }
+}
+
+impl IntoFuture for RequestHandle {
+    type Output = Result<Frame>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Result<Frame>> + Send>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.response())
+    }
+}
+
+/// Limits on requests waiting for a reply from the server
+///
+/// Services that match replies to requests, such as the App service, keep
+/// each request until its reply arrives. These limits keep a server that
+/// never replies, or a client firing many requests, from growing that
+/// bookkeeping without bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestLimits {
+    /// How long a request waits for its reply before failing with
+    /// [`Error::Timeout`] (None to wait as long as the service runs)
+    ///
+    /// Expired requests are swept periodically, so a request may wait up to
+    /// a quarter of the timeout longer.
+    pub timeout: Option<Duration>,
+
+    /// Number of requests that may wait for a reply at once; further
+    /// requests fail right away with [`Error::Service`]
+    pub max_pending: usize,
+}
+
+impl RequestLimits {
+    /// Default reply timeout
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Default number of requests waiting at once
+    pub const DEFAULT_MAX_PENDING: usize = 256;
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Some(Self::DEFAULT_TIMEOUT),
+            max_pending: Self::DEFAULT_MAX_PENDING,
+        }
+    }
+}
+
+/// Settings of a single service
+///
+/// Set with [`ClientBuilder::service_config`](crate::ClientBuilder::service_config).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServiceConfig {
+    /// Number of inbound frames the server may send ahead of the handler
+    /// (None to leave the server unrestricted)
+    ///
+    /// The client grants the whole window when it subscribes, then grants
+    /// more each time the handler has consumed half of it, so a slow handler
+    /// holds the server back instead of letting frames pile up. Only servers
+    /// supporting flow control respect the window; others ignore the
+    /// [`FLOW_CREDIT`](crate::command::FLOW_CREDIT) frames.
+    pub window_size: Option<u32>,
 }
 
 /// Factory for creating service instances
@@ -213,16 +951,137 @@ pub struct ServiceFactory;
 
 impl ServiceFactory {
     /// Create a new service instance
+    ///
+    /// Custom services get a [`builtin::CustomService`], which passes their
+    /// frames through as they are.
     pub fn create(service_type: ServiceType) -> Option<Box<dyn Service>> {
+        Self::create_with_limits(service_type, RequestLimits::default())
+    }
+
+    /// Create a new service instance whose pending requests are bounded by
+    /// `limits`
+    pub fn create_with_limits(
+        service_type: ServiceType,
+        limits: RequestLimits,
+    ) -> Option<Box<dyn Service>> {
+        Self::create_handler(service_type, limits).map(ServiceHandler::into_boxed)
+    }
+
+    /// Create a new service instance for a service handler, dispatched
+    /// statically
+    pub(crate) fn create_handler(
+        service_type: ServiceType,
+        limits: RequestLimits,
+    ) -> Option<ServiceHandler> {
         match service_type {
-            ServiceType::Display => Some(Box::new(builtin::DisplayService::new())),
-            ServiceType::Input => Some(Box::new(builtin::InputService::new())),
-            ServiceType::Clipboard => Some(Box::new(builtin::ClipboardService::new())),
-            ServiceType::FileTransfer => Some(Box::new(builtin::FileTransferService::new())),
-            ServiceType::App => Some(Box::new(builtin::AppService::new())),
-            _ => None,
+            ServiceType::Display => Some(ServiceHandler::Display(builtin::DisplayService::new())),
+            ServiceType::Input => Some(ServiceHandler::Input(builtin::InputService::new())),
+            ServiceType::Audio => Some(ServiceHandler::Audio(builtin::AudioService::new())),
+            ServiceType::Clipboard => {
+                Some(ServiceHandler::Clipboard(builtin::ClipboardService::new()))
+            }
+            ServiceType::FileTransfer => Some(ServiceHandler::FileTransfer(
+                builtin::FileTransferService::new(),
+            )),
+            ServiceType::App => Some(ServiceHandler::App(builtin::AppService::with_limits(
+                limits,
+            ))),
+            ServiceType::Custom(id) => {
+                Some(ServiceHandler::Custom(builtin::CustomService::new(id)))
+            }
+        }
+    }
+}
+
+/// Service instance run by a service handler
+///
+/// Messages to built-in services are dispatched statically, so the streaming
+/// path pays for neither a virtual call nor a boxed future per message. The
+/// rarely used lifecycle methods still go through the [`Service`] trait.
+/// [`ServiceFactory::create`] keeps returning trait objects for code that
+/// drives services itself.
+pub(crate) enum ServiceHandler {
+    Display(builtin::DisplayService),
+    Input(builtin::InputService),
+    Audio(builtin::AudioService),
+    Clipboard(builtin::ClipboardService),
+    FileTransfer(builtin::FileTransferService),
+    App(builtin::AppService),
+    Custom(builtin::CustomService),
+}
+
+impl ServiceHandler {
+    /// The service as a trait object
+    fn as_service(&mut self) -> &mut dyn Service {
+        match self {
+            Self::Display(service) => service,
+            Self::Input(service) => service,
+            Self::Audio(service) => service,
+            Self::Clipboard(service) => service,
+            Self::FileTransfer(service) => service,
+            Self::App(service) => service,
+            Self::Custom(service) => service,
+        }
+    }
+
+    /// Box the service as a trait object
+    fn into_boxed(self) -> Box<dyn Service> {
+        match self {
+            Self::Display(service) => Box::new(service),
+            Self::Input(service) => Box::new(service),
+            Self::Audio(service) => Box::new(service),
+            Self::Clipboard(service) => Box::new(service),
+            Self::FileTransfer(service) => Box::new(service),
+            Self::App(service) => Box::new(service),
+            Self::Custom(service) => Box::new(service),
+        }
+    }
+
+    /// Get the type of the service
+    pub(crate) fn service_type(&self) -> ServiceType {
+        match self {
+            Self::Display(_) => ServiceType::Display,
+            Self::Input(_) => ServiceType::Input,
+            Self::Audio(_) => ServiceType::Audio,
+            Self::Clipboard(_) => ServiceType::Clipboard,
+            Self::FileTransfer(_) => ServiceType::FileTransfer,
+            Self::App(_) => ServiceType::App,
+            Self::Custom(service) => service.service_type(),
         }
     }
+
+    /// Start the service
+    pub(crate) async fn start(&mut self) -> Result<()> {
+        self.as_service().start().await
+    }
+
+    /// Stop the service
+    pub(crate) async fn stop(&mut self) -> Result<()> {
+        self.as_service().stop().await
+    }
+
+    /// Handle an incoming message
+    pub(crate) async fn handle_message(&mut self, message: ServiceMessage) -> Result<()> {
+        match self {
+            Self::Display(service) => service.process_message(message).await,
+            Self::Input(service) => service.process_message(message).await,
+            Self::Audio(service) => service.process_message(message).await,
+            Self::Clipboard(service) => service.process_message(message).await,
+            Self::FileTransfer(service) => service.process_message(message).await,
+            Self::App(service) => service.process_message(message).await,
+            Self::Custom(service) => service.process_message(message).await,
+        }
+    }
+
+    /// Called once the server has acknowledged the subscription
+    pub(crate) async fn on_subscribed(&mut self, client: &ServiceClient) -> Result<()> {
+        self.as_service().on_subscribed(client).await
+    }
+
+    /// Called when the service is unsubscribed while still connected
+    pub(crate) async fn on_unsubscribed(&mut self, client: &ServiceClient) -> Result<()> {
+        self.as_service().on_unsubscribed(client).await
+    }
 }
 
 /// Built-in service implementations
@@ -243,10 +1102,48 @@ pub mod builtin {
         pub fn new() -> Self {
             Self {}
         }
+
+        /// Handle an incoming message
+        pub(crate) async fn process_message(&mut self, message: ServiceMessage) -> Result<()> {
+            trace!("Display service handling message: {:?}", message.id);
+
+            // Process message based on command ID
+            match parse_command(&message.frame) {
+                Some(ParsedCommand::DisplayInfo) => {
+                    // Parse display info and store it
+                    // For now, just acknowledge receipt
+                    if let Some(tx) = message.response_tx {
+                        let response = Frame::new(CommandId::Ack as u8, Vec::new());
+                        let _ = tx.send(Ok(response));
+                    }
+                }
+                Some(ParsedCommand::StreamFrame) => {
+                    // Process frame data (e.g., decode and display)
+                    // No response needed for streaming data
+                }
+                _ => {
+                    debug!(
+                        "Unknown command for display service: {:02x}",
+                        message.frame.command_id()
+                    );
+                    if let Some(tx) = message.response_tx {
+                        let response =
+                            Frame::new(CommandId::Error as u8, b"Unknown command".to_vec());
+                        let _ = tx.send(Ok(response));
+                    }
+                }
+            }
+
+            Ok(())
+        }
     }
 
     #[async_trait::async_trait]
     impl Service for DisplayService {
+        fn service_type(&self) -> ServiceType {
+            ServiceType::Display
+        }
+
         async fn start(&mut self) -> Result<()> {
             debug!("Starting display service");
             Ok(())
@@ -257,26 +1154,53 @@ pub mod builtin {
             Ok(())
         }
 
+        async fn on_subscribed(&mut self, client: &ServiceClient) -> Result<()> {
+            // Ask for the current display layout
+            client
+                .send_fire_and_forget(Frame::new(CommandId::DisplayInfo as u8, Vec::new()))
+                .await
+        }
+
         async fn handle_message(&mut self, message: ServiceMessage) -> Result<()> {
-            trace!("Display service handling message: {:?}", message.id);
+            self.process_message(message).await
+        }
+    }
+
+    /// Audio service implementation
+    pub struct AudioService {}
+
+    impl Default for AudioService {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl AudioService {
+        /// Create a new audio service
+        pub fn new() -> Self {
+            Self {}
+        }
+
+        /// Handle an incoming message
+        pub(crate) async fn process_message(&mut self, message: ServiceMessage) -> Result<()> {
+            trace!("Audio service handling message: {:?}", message.id);
 
             // Process message based on command ID
-            match message.frame.command_id() {
-                cmd if cmd == CommandId::DisplayInfo as u8 => {
-                    // Parse display info and store it
-                    // For now, just acknowledge receipt
+            match parse_command(&message.frame) {
+                Some(ParsedCommand::AudioFormat) => {
+                    // The client does not decode audio, so a format change
+                    // needs no setup; requests carrying one are acknowledged
                     if let Some(tx) = message.response_tx {
                         let response = Frame::new(CommandId::Ack as u8, Vec::new());
                         let _ = tx.send(Ok(response));
                     }
                 }
-                cmd if cmd == CommandId::StreamFrame as u8 => {
-                    // Process frame data (e.g., decode and display)
-                    // No response needed for streaming data
+                Some(ParsedCommand::AudioStream) => {
+                    // Streaming data is consumed without a response
                 }
                 _ => {
                     debug!(
-                        "Unknown command for display service: {:02x}",
+                        "Unknown command for audio service: {:02x}",
                         message.frame.command_id()
                     );
                     if let Some(tx) = message.response_tx {
@@ -291,6 +1215,27 @@ pub mod builtin {
         }
     }
 
+    #[async_trait::async_trait]
+    impl Service for AudioService {
+        fn service_type(&self) -> ServiceType {
+            ServiceType::Audio
+        }
+
+        async fn start(&mut self) -> Result<()> {
+            debug!("Starting audio service");
+            Ok(())
+        }
+
+        async fn stop(&mut self) -> Result<()> {
+            debug!("Stopping audio service");
+            Ok(())
+        }
+
+        async fn handle_message(&mut self, message: ServiceMessage) -> Result<()> {
+            self.process_message(message).await
+        }
+    }
+
     /// Input service implementation
     pub struct InputService {}
 
@@ -305,10 +1250,27 @@ pub mod builtin {
         pub fn new() -> Self {
             Self {}
         }
+
+        /// Handle an incoming message
+        pub(crate) async fn process_message(&mut self, message: ServiceMessage) -> Result<()> {
+            trace!("Input service handling message: {:?}", message.id);
+
+            // Basic acknowledgment for now
+            if let Some(tx) = message.response_tx {
+                let response = Frame::new(CommandId::Ack as u8, Vec::new());
+                let _ = tx.send(Ok(response));
+            }
+
+            Ok(())
+        }
     }
 
     #[async_trait::async_trait]
     impl Service for InputService {
+        fn service_type(&self) -> ServiceType {
+            ServiceType::Input
+        }
+
         async fn start(&mut self) -> Result<()> {
             debug!("Starting input service");
             Ok(())
@@ -320,20 +1282,15 @@ pub mod builtin {
         }
 
         async fn handle_message(&mut self, message: ServiceMessage) -> Result<()> {
-            trace!("Input service handling message: {:?}", message.id);
-
-            // Basic acknowledgment for now
-            if let Some(tx) = message.response_tx {
-                let response = Frame::new(CommandId::Ack as u8, Vec::new());
-                let _ = tx.send(Ok(response));
-            }
-
-            Ok(())
+            self.process_message(message).await
         }
     }
 
     /// Clipboard service implementation
-    pub struct ClipboardService {}
+    pub struct ClipboardService {
+        /// Most recent clipboard contents seen on the service
+        contents: Option<ClipboardData>,
+    }
 
     impl Default for ClipboardService {
         fn default() -> Self {
@@ -344,12 +1301,44 @@ pub mod builtin {
     impl ClipboardService {
         /// Create a new clipboard service
         pub fn new() -> Self {
-            Self {}
+            Self { contents: None }
+        }
+
+        /// Get the most recent clipboard contents seen on the service
+        pub fn contents(&self) -> Option<&ClipboardData> {
+            self.contents.as_ref()
+        }
+
+        /// Handle an incoming message
+        pub(crate) async fn process_message(&mut self, message: ServiceMessage) -> Result<()> {
+            trace!("Clipboard service handling message: {:?}", message.id);
+
+            if parse_command(&message.frame) == Some(ParsedCommand::ClipboardData) {
+                let data = ClipboardData::from_frame(&message.frame)?;
+                debug!(
+                    "Clipboard contents: {} ({} bytes)",
+                    data.mime_type,
+                    data.bytes.len()
+                );
+                self.contents = Some(data);
+            }
+
+            // Basic acknowledgment for now
+            if let Some(tx) = message.response_tx {
+                let response = Frame::new(CommandId::Ack as u8, Vec::new());
+                let _ = tx.send(Ok(response));
+            }
+
+            Ok(())
         }
     }
 
     #[async_trait::async_trait]
     impl Service for ClipboardService {
+        fn service_type(&self) -> ServiceType {
+            ServiceType::Clipboard
+        }
+
         async fn start(&mut self) -> Result<()> {
             debug!("Starting clipboard service");
             Ok(())
@@ -361,15 +1350,7 @@ pub mod builtin {
         }
 
         async fn handle_message(&mut self, message: ServiceMessage) -> Result<()> {
-            trace!("Clipboard service handling message: {:?}", message.id);
-
-            // Basic acknowledgment for now
-            if let Some(tx) = message.response_tx {
-                let response = Frame::new(CommandId::Ack as u8, Vec::new());
-                let _ = tx.send(Ok(response));
-            }
-
-            Ok(())
+            self.process_message(message).await
         }
     }
 
@@ -387,10 +1368,27 @@ pub mod builtin {
         pub fn new() -> Self {
             Self {}
         }
+
+        /// Handle an incoming message
+        pub(crate) async fn process_message(&mut self, message: ServiceMessage) -> Result<()> {
+            trace!("File transfer service handling message: {:?}", message.id);
+
+            // Basic acknowledgment for now
+            if let Some(tx) = message.response_tx {
+                let response = Frame::new(CommandId::Ack as u8, Vec::new());
+                let _ = tx.send(Ok(response));
+            }
+
+            Ok(())
+        }
     }
 
     #[async_trait::async_trait]
     impl Service for FileTransferService {
+        fn service_type(&self) -> ServiceType {
+            ServiceType::FileTransfer
+        }
+
         async fn start(&mut self) -> Result<()> {
             debug!("Starting file transfer service");
             Ok(())
@@ -402,20 +1400,33 @@ pub mod builtin {
         }
 
         async fn handle_message(&mut self, message: ServiceMessage) -> Result<()> {
-            trace!("File transfer service handling message: {:?}", message.id);
-
-            // Basic acknowledgment for now
-            if let Some(tx) = message.response_tx {
-                let response = Frame::new(CommandId::Ack as u8, Vec::new());
-                let _ = tx.send(Ok(response));
-            }
-
-            Ok(())
+            self.process_message(message).await
         }
     }
 
-    /// App service implementation for launching applications
-    pub struct AppService {}
+    /// Request waiting for the server's reply
+    struct PendingReply {
+        tx: oneshot::Sender<Result<Frame>>,
+        sent_at: Instant,
+    }
+
+    type PendingMap = std::sync::Mutex<HashMap<Uuid, PendingReply>>;
+
+    /// App service implementation for launching and managing applications
+    ///
+    /// Requests waiting for a reply are bounded by [`RequestLimits`]. While
+    /// the service runs, a background task sweeps requests that have waited
+    /// longer than the timeout, and those whose caller gave up.
+    pub struct AppService {
+        /// Requests waiting for the server's reply, by request ID
+        pending: Arc<PendingMap>,
+
+        /// Limits on the pending requests
+        limits: RequestLimits,
+
+        /// Task expiring pending requests, while started
+        sweeper: Option<tokio::task::JoinHandle<()>>,
+    }
 
     impl Default for AppService {
         fn default() -> Self {
@@ -424,30 +1435,103 @@ pub mod builtin {
     }
 
     impl AppService {
-        /// Create a new app service
+        /// Create a new app service with the default request limits
         pub fn new() -> Self {
-            Self {}
+            Self::with_limits(RequestLimits::default())
         }
-    }
 
-    #[async_trait::async_trait]
-    impl Service for AppService {
-        async fn start(&mut self) -> Result<()> {
-            debug!("Starting app service");
-            Ok(())
+        /// Create a new app service with the given request limits
+        pub fn with_limits(limits: RequestLimits) -> Self {
+            Self {
+                pending: Arc::new(std::sync::Mutex::new(HashMap::new())),
+                limits,
+                sweeper: None,
+            }
         }
 
-        async fn stop(&mut self) -> Result<()> {
-            debug!("Stopping app service");
+        /// Wait for the reply to a request, identified by the request ID at
+        /// the start of its payload
+        ///
+        /// Fails for requests that are refused, which are then not sent.
+        fn await_reply(&mut self, message: ServiceMessage) -> Result<()> {
+            let Some(tx) = message.response_tx else {
+                return Ok(());
+            };
+            let Some(id) = message.frame.payload().get(..16) else {
+                let response = Frame::new(CommandId::Error as u8, b"Missing request ID".to_vec());
+                let _ = tx.send(Ok(response));
+                return Err(Error::Service("App request without request ID".to_string()));
+            };
+
+            let mut pending = self.pending.lock().unwrap();
+            if pending.len() >= self.limits.max_pending {
+                pending.retain(|_, reply| !reply.tx.is_closed());
+            }
+            if pending.len() >= self.limits.max_pending {
+                let reason = format!(
+                    "Too many app requests waiting for a reply (limit {})",
+                    self.limits.max_pending
+                );
+                let _ = tx.send(Err(Error::Service(reason.clone())));
+                return Err(Error::Service(reason));
+            }
+            let id = Uuid::from_slice(id).expect("16-byte request ID");
+            pending.insert(
+                id,
+                PendingReply {
+                    tx,
+                    sent_at: Instant::now(),
+                },
+            );
             Ok(())
         }
 
-        async fn handle_message(&mut self, message: ServiceMessage) -> Result<()> {
+        /// Pass a reply from the server to the request waiting for it
+        fn complete_request(&mut self, request_id: Uuid, frame: Frame) {
+            match self.pending.lock().unwrap().remove(&request_id) {
+                Some(reply) => {
+                    let _ = reply.tx.send(Ok(frame));
+                }
+                None => debug!("Reply to unknown app request {}", request_id),
+            }
+        }
+
+        /// Periodically fail requests that waited longer than `timeout`, and
+        /// drop those nobody waits for anymore
+        async fn sweep(pending: std::sync::Weak<PendingMap>, timeout: Duration) {
+            let mut interval = time::interval((timeout / 4).max(Duration::from_millis(1)));
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let Some(pending) = pending.upgrade() else {
+                    return;
+                };
+                let now = Instant::now();
+                let mut pending = pending.lock().unwrap();
+                pending.retain(|_, reply| !reply.tx.is_closed());
+                let expired: Vec<Uuid> = pending
+                    .iter()
+                    .filter(|(_, reply)| now.duration_since(reply.sent_at) >= timeout)
+                    .map(|(id, _)| *id)
+                    .collect();
+                for id in expired {
+                    let reply = pending.remove(&id).expect("expired request");
+                    debug!("App request {} timed out", id);
+                    let _ = reply.tx.send(Err(Error::Timeout(format!(
+                        "No reply to app request {} within {:?}",
+                        id, timeout
+                    ))));
+                }
+            }
+        }
+
+        /// Handle an incoming message
+        pub(crate) async fn process_message(&mut self, message: ServiceMessage) -> Result<()> {
             trace!("App service handling message: {:?}", message.id);
 
             // Process message based on command ID
-            match message.frame.command_id() {
-                cmd if cmd == CommandId::LaunchApp as u8 => {
+            match parse_command(&message.frame) {
+                Some(ParsedCommand::LaunchApp) => {
                     debug!("Handling LaunchApp command");
                     // Process launch app command
                     // Just forward to the server, no special handling needed client-side
@@ -456,6 +1540,17 @@ pub mod builtin {
                         let _ = tx.send(Ok(response));
                     }
                 }
+                Some(ParsedCommand::ListApps | ParsedCommand::TerminateApp) => {
+                    self.await_reply(message)?
+                }
+                Some(ParsedCommand::AppList) => {
+                    let list = AppList::from_frame(&message.frame)?;
+                    self.complete_request(list.request_id, message.frame);
+                }
+                Some(ParsedCommand::AppTerminated) => {
+                    let terminated = AppTerminated::from_frame(&message.frame)?;
+                    self.complete_request(terminated.request_id, message.frame);
+                }
                 _ => {
                     debug!(
                         "Unknown command for app service: {:02x}",
@@ -472,4 +1567,89 @@ pub mod builtin {
             Ok(())
         }
     }
+
+    #[async_trait::async_trait]
+    impl Service for AppService {
+        fn service_type(&self) -> ServiceType {
+            ServiceType::App
+        }
+
+        async fn start(&mut self) -> Result<()> {
+            debug!("Starting app service");
+            if let Some(timeout) = self.limits.timeout {
+                let pending = Arc::downgrade(&self.pending);
+                self.sweeper = Some(tokio::spawn(Self::sweep(pending, timeout)));
+            }
+            Ok(())
+        }
+
+        async fn stop(&mut self) -> Result<()> {
+            debug!("Stopping app service");
+            if let Some(sweeper) = self.sweeper.take() {
+                sweeper.abort();
+            }
+
+            // Requests still waiting fail instead of hanging
+            self.pending.lock().unwrap().clear();
+            Ok(())
+        }
+
+        async fn handle_message(&mut self, message: ServiceMessage) -> Result<()> {
+            self.process_message(message).await
+        }
+    }
+
+    /// Custom service implementation, passing frames through to the server
+    ///
+    /// The client knows nothing about what custom services carry, so frames
+    /// sent through them are written as they are, and requests are answered
+    /// with an `Ack` right away.
+    pub struct CustomService {
+        /// Server-defined ID of the service
+        id: u8,
+    }
+
+    impl CustomService {
+        /// Create a custom service with the given ID
+        pub fn new(id: u8) -> Self {
+            Self { id }
+        }
+
+        /// Handle an incoming message
+        pub(crate) async fn process_message(&mut self, message: ServiceMessage) -> Result<()> {
+            trace!(
+                "Custom service {} handling message: {:?}",
+                self.id,
+                message.id
+            );
+
+            if let Some(tx) = message.response_tx {
+                let response = Frame::new(CommandId::Ack as u8, Vec::new());
+                let _ = tx.send(Ok(response));
+            }
+
+            Ok(())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Service for CustomService {
+        fn service_type(&self) -> ServiceType {
+            ServiceType::Custom(self.id)
+        }
+
+        async fn start(&mut self) -> Result<()> {
+            debug!("Starting custom service {}", self.id);
+            Ok(())
+        }
+
+        async fn stop(&mut self) -> Result<()> {
+            debug!("Stopping custom service {}", self.id);
+            Ok(())
+        }
+
+        async fn handle_message(&mut self, message: ServiceMessage) -> Result<()> {
+            self.process_message(message).await
+        }
+    }
 }
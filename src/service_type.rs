@@ -0,0 +1,184 @@
+//! Service types and their scheduling priorities
+//!
+//! These are plain data and available without the `client` feature.
+
+use crate::command::{self, ParsedCommand};
+use rcpcore::CommandId;
+use std::fmt;
+use std::str::FromStr;
+
+/// Service type enumeration
+///
+/// On the wire, services are addressed by name: subscription, acknowledgement
+/// and unsubscription frames carry the [`Display`](fmt::Display) form of the
+/// service type as their payload. Built-in services are named by
+/// [`as_str`](Self::as_str), and custom services `custom:<id>`, e.g.
+/// `custom:42` for `Custom(42)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServiceType {
+    /// Display service for screen sharing
+    Display,
+
+    /// Input service for sending keyboard/mouse events
+    Input,
+
+    /// Audio service for streaming audio
+    Audio,
+
+    /// Clipboard service for clipboard synchronization
+    Clipboard,
+
+    /// File transfer service
+    FileTransfer,
+
+    /// Application launching service
+    App,
+
+    /// Custom service, identified by a server-defined ID
+    Custom(u8),
+}
+
+impl ServiceType {
+    /// All built-in service types
+    pub const BUILTIN: [ServiceType; 6] = [
+        Self::Display,
+        Self::Input,
+        Self::Audio,
+        Self::Clipboard,
+        Self::FileTransfer,
+        Self::App,
+    ];
+
+    /// Get the string representation of a service type
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Display => "display",
+            Self::Input => "input",
+            Self::Audio => "audio",
+            Self::Clipboard => "clipboard",
+            Self::FileTransfer => "file-transfer",
+            Self::App => "app",
+            Self::Custom(_) => "custom",
+        }
+    }
+
+    /// Get the command ID for subscribing to this service
+    ///
+    /// Services without a dedicated subscription command use the generic
+    /// `ServiceSubscribe`, which the server tells apart by the service name
+    /// in the payload.
+    pub fn subscription_command(&self) -> u8 {
+        match self {
+            Self::Display => CommandId::SubscribeDisplay as u8,
+            Self::Input => CommandId::SubscribeInput as u8,
+            Self::Audio => CommandId::SubscribeAudio as u8,
+            Self::Clipboard => CommandId::SubscribeClipboard as u8,
+            Self::FileTransfer => CommandId::SubscribeFileTransfer as u8,
+            Self::App | Self::Custom(_) => CommandId::ServiceSubscribe as u8,
+        }
+    }
+
+    /// Get the inbound command IDs that are routed to this service.
+    ///
+    /// This is the single place that decides which server frames a service
+    /// receives; routing a new command only requires adding it here.
+    pub fn routed_commands(&self) -> &'static [u8] {
+        match self {
+            Self::Display => &[CommandId::StreamFrame as u8, CommandId::DisplayInfo as u8],
+            Self::Input => &[],
+            Self::Audio => &[command::AUDIO_STREAM, command::AUDIO_FORMAT],
+            Self::Clipboard => &[command::CLIPBOARD_DATA],
+            Self::FileTransfer => &[],
+            Self::App => &[command::APP_LIST, command::APP_TERMINATED],
+            Self::Custom(_) => &[],
+        }
+    }
+
+    /// Whether the service's data uses the data channel when the client keeps
+    /// a separate control channel
+    pub fn uses_data_channel(&self) -> bool {
+        matches!(self, Self::Display | Self::Audio)
+    }
+
+    /// Whether frames of this service that exceed the maximum frame size are
+    /// split into chunks rather than sent whole
+    pub fn splits_large_frames(&self) -> bool {
+        matches!(self, Self::Clipboard | Self::FileTransfer)
+    }
+
+    /// Get the default scheduling priority of frames sent by this service
+    pub fn default_priority(&self) -> FramePriority {
+        match self {
+            Self::Input => FramePriority::High,
+            Self::FileTransfer => FramePriority::Low,
+            _ => FramePriority::Normal,
+        }
+    }
+
+    /// Find the built-in service that inbound frames with this command ID are routed to
+    pub fn for_command(command_id: u8) -> Option<ServiceType> {
+        Self::BUILTIN
+            .into_iter()
+            .find(|service_type| service_type.routed_commands().contains(&command_id))
+    }
+}
+
+impl FromStr for ServiceType {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "display" => Ok(Self::Display),
+            "input" => Ok(Self::Input),
+            "audio" => Ok(Self::Audio),
+            "clipboard" => Ok(Self::Clipboard),
+            "file-transfer" => Ok(Self::FileTransfer),
+            "app" => Ok(Self::App),
+            name => name
+                .strip_prefix("custom:")
+                .and_then(|id| id.parse().ok())
+                .map(Self::Custom)
+                .ok_or(()),
+        }
+    }
+}
+
+impl fmt::Display for ServiceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Custom(id) => write!(f, "{}:{}", self.as_str(), id),
+            _ => write!(f, "{}", self.as_str()),
+        }
+    }
+}
+
+/// Scheduling priority of an outbound frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FramePriority {
+    /// Bulk traffic such as file transfer chunks
+    Low,
+
+    /// Regular service traffic
+    Normal,
+
+    /// Latency-sensitive traffic such as input; never delayed by the egress
+    /// rate cap
+    High,
+}
+
+impl FramePriority {
+    /// Priority of protocol control commands, which is not configurable
+    ///
+    /// Returns `None` for commands whose priority depends on the service
+    /// sending them.
+    pub fn for_command(command_id: u8) -> Option<FramePriority> {
+        match ParsedCommand::from_id(command_id)? {
+            ParsedCommand::Heartbeat
+            | ParsedCommand::Error
+            | ParsedCommand::Ack
+            | ParsedCommand::Cancel
+            | ParsedCommand::FlowCredit => Some(Self::High),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! Session parameters pushed by the server
+//!
+//! A server can update session parameters mid-session with a
+//! [`CONFIG_UPDATE`](command::CONFIG_UPDATE) frame. Not every parameter is
+//! safe to take from the server, so each field documents whether the client
+//! applies it to the live session or only reports it.
+
+use crate::{
+    command,
+    error::{Error, Result},
+};
+use rcpcore::Frame;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Session parameters updated by the server
+///
+/// Fields left as None are unchanged. Applied updates last for the rest of
+/// the session; a new session starts from the client configuration again.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionConfigUpdate {
+    /// New keep-alive interval in seconds, 0 to disable the liveness check.
+    /// Applied immediately.
+    pub keep_alive_secs: Option<u64>,
+
+    /// New number of silent keep-alive intervals before the connection is
+    /// declared dead, 0 to disable the liveness check. Applied immediately.
+    pub heartbeat_miss_count: Option<u32>,
+
+    /// Maximum frame size the server accepts. Ignored: the client's own
+    /// limit guards it against misbehaving servers, so the server cannot
+    /// change it.
+    pub max_frame_size: Option<u64>,
+
+    /// Server feature toggles. Not interpreted by the client; they are only
+    /// passed on to the application in the
+    /// [`ConfigUpdated`](crate::ClientEvent::ConfigUpdated) event.
+    pub features: HashMap<String, bool>,
+}
+
+impl SessionConfigUpdate {
+    /// Encode the update into a config update frame
+    pub fn to_frame(&self) -> Result<Frame> {
+        let payload = rcpcore::utils::to_bytes(self)?;
+        Ok(Frame::new(command::CONFIG_UPDATE, payload))
+    }
+
+    /// Decode the update from a config update frame
+    pub fn from_frame(frame: &Frame) -> Result<Self> {
+        if frame.command_id() != command::CONFIG_UPDATE {
+            return Err(Error::Protocol(format!(
+                "Expected config update frame, got command {:02x}",
+                frame.command_id()
+            )));
+        }
+        Ok(rcpcore::utils::from_bytes(frame.payload())?)
+    }
+}
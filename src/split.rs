@@ -0,0 +1,160 @@
+//! One-directional stream adapters
+//!
+//! rcpcore's `Protocol` runs over a stream that is both readable and
+//! writable. These adapters let it run over one half of a split stream, so
+//! reading and writing can be owned by separate tasks without sharing a
+//! lock. Using a half in the direction it was not split for fails with
+//! [`io::ErrorKind::Unsupported`].
+//!
+//! [`StreamReader`] and [`StreamWriter`] are the halves of the streams a
+//! client runs over.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+#[cfg(feature = "test-util")]
+use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::tcp::{OwnedReadHalf, OwnedWriteHalf},
+};
+
+/// Read half of a split stream
+#[derive(Debug)]
+pub(crate) struct ReadOnly<R>(pub(crate) R);
+
+/// Write half of a split stream
+#[derive(Debug)]
+pub(crate) struct WriteOnly<W>(pub(crate) W);
+
+/// Read half of a client connection's stream
+#[derive(Debug)]
+pub(crate) enum StreamReader {
+    /// TCP connection to the server
+    Tcp(OwnedReadHalf),
+
+    /// In-memory stream of a client created for tests
+    #[cfg(feature = "test-util")]
+    Test(ReadHalf<DuplexStream>),
+}
+
+/// Write half of a client connection's stream
+#[derive(Debug)]
+pub(crate) enum StreamWriter {
+    /// TCP connection to the server
+    Tcp(OwnedWriteHalf),
+
+    /// In-memory stream of a client created for tests
+    #[cfg(feature = "test-util")]
+    Test(WriteHalf<DuplexStream>),
+}
+
+/// Error returned when a half is used in the wrong direction
+fn unsupported(direction: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} on the wrong half of a split stream", direction),
+    )
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ReadOnly<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl<R: Unpin> AsyncWrite for ReadOnly<R> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Err(unsupported("write")))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: Unpin> AsyncRead for WriteOnly<W> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Poll::Ready(Err(unsupported("read")))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for WriteOnly<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+impl AsyncRead for StreamReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(half) => Pin::new(half).poll_read(cx, buf),
+            #[cfg(feature = "test-util")]
+            Self::Test(half) => Pin::new(half).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for StreamWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(half) => Pin::new(half).poll_write(cx, buf),
+            #[cfg(feature = "test-util")]
+            Self::Test(half) => Pin::new(half).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(half) => Pin::new(half).poll_flush(cx),
+            #[cfg(feature = "test-util")]
+            Self::Test(half) => Pin::new(half).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(half) => Pin::new(half).poll_shutdown(cx),
+            #[cfg(feature = "test-util")]
+            Self::Test(half) => Pin::new(half).poll_shutdown(cx),
+        }
+    }
+}
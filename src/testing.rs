@@ -0,0 +1,247 @@
+//! Utilities for tests and examples
+//!
+//! [`LoopbackServer`] is a minimal RCP server running in-process, so the
+//! client can be exercised, and its examples run, without a real server.
+
+use crate::{
+    client::NAMED_SERVICE_PREFIX,
+    command::{parse_command, ParsedCommand},
+    error::{Error, Result},
+    heartbeat::{self, HeartbeatPayload},
+    room::{RoomAction, RoomOutcome, RoomRequest, RoomStatus},
+    service::{AppList, AppTerminated, TerminateOutcome},
+};
+use log::debug;
+use rcpcore::{AuthChallenge, AuthPayload, CommandId, Frame, Protocol, SessionInfo};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// In-process RCP server for tests and examples
+///
+/// Listens on an ephemeral localhost port and serves any number of clients
+/// until dropped. It implements just enough of the protocol for the client
+/// to work against it:
+///
+/// - authentication succeeds with any credentials
+/// - data channels are accepted
+/// - subscriptions are acknowledged, and named services are assigned IDs
+///   counting up from 1 for each connection
+/// - heartbeats are answered right away, echoing their timestamp, so
+///   [`Client::ping`](crate::Client::ping) works
+/// - application listings are answered with no applications, and
+///   terminating one reports that it is not running
+/// - any room can be joined, as its only participant, and left
+/// - other service frames are echoed back unchanged
+///
+/// ```
+/// use rcpcli::{testing::LoopbackServer, Client};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> rcpcli::Result<()> {
+/// let server = LoopbackServer::start().await?;
+/// let client = Client::builder()
+///     .host("127.0.0.1")
+///     .port(server.port())
+///     .auth_psk("any-key")
+///     .build();
+/// client.connect_and_authenticate().await?;
+/// client.disconnect().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct LoopbackServer {
+    /// Address the server is listening on
+    addr: SocketAddr,
+
+    /// Accept loop, owning the connection tasks
+    task: JoinHandle<()>,
+}
+
+impl LoopbackServer {
+    /// Start a server on an ephemeral localhost port
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let task = tokio::spawn(async move {
+            // Dropping the set when the loop is aborted closes every connection
+            let mut connections = JoinSet::new();
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, peer)) => {
+                            connections.spawn(async move {
+                                if let Err(e) = serve(stream).await {
+                                    debug!("Loopback connection from {} failed: {}", peer, e);
+                                }
+                            });
+                        }
+                        Err(e) => {
+                            debug!("Loopback server stopped accepting: {}", e);
+                            break;
+                        }
+                    },
+                    Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                }
+            }
+        });
+        Ok(Self { addr, task })
+    }
+
+    /// Get the address the server is listening on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Get the port the server is listening on
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+}
+
+impl Drop for LoopbackServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Serve one connection until the client closes it
+async fn serve(stream: TcpStream) -> Result<()> {
+    let mut protocol = Protocol::new(stream);
+    let Some(first) = protocol.read_frame().await? else {
+        return Ok(());
+    };
+    match parse_command(&first) {
+        Some(ParsedCommand::Auth) => authenticate(&mut protocol, &first).await?,
+        Some(ParsedCommand::DataChannel) => {
+            protocol
+                .write_frame(&Frame::new(CommandId::Ack as u8, Vec::new()))
+                .await?
+        }
+        _ => {
+            return Err(Error::Protocol(format!(
+                "Unexpected first frame {:02x}",
+                first.command_id()
+            )))
+        }
+    }
+
+    let epoch = Instant::now();
+    let mut named_services = HashMap::new();
+    while let Some(frame) = protocol.read_frame().await? {
+        let reply = match parse_command(&frame) {
+            Some(ParsedCommand::Heartbeat) => {
+                let echo = HeartbeatPayload::decode(frame.payload());
+                let payload = HeartbeatPayload {
+                    timestamp_micros: epoch.elapsed().as_micros() as u64 + 1,
+                    wall_clock_micros: heartbeat::wall_clock_micros(),
+                    echo_micros: echo.map(|echo| echo.timestamp_micros),
+                };
+                Frame::new(CommandId::Heartbeat as u8, payload.encode())
+            }
+            Some(ParsedCommand::ServiceSubscribe)
+                if frame.payload().starts_with(NAMED_SERVICE_PREFIX.as_bytes()) =>
+            {
+                // Named services are answered with the ID they were assigned
+                let name = String::from_utf8_lossy(&frame.payload()[NAMED_SERVICE_PREFIX.len()..])
+                    .into_owned();
+                let next = named_services.len() + 1;
+                let id = *named_services.entry(name.clone()).or_insert(next);
+                let id = u8::try_from(id)
+                    .map_err(|_| Error::Protocol("No service IDs left".to_string()))?;
+                let payload = format!("{}{}={}", NAMED_SERVICE_PREFIX, name, id);
+                Frame::new(CommandId::Ack as u8, payload.into_bytes())
+            }
+            Some(
+                ParsedCommand::SubscribeDisplay
+                | ParsedCommand::SubscribeInput
+                | ParsedCommand::SubscribeAudio
+                | ParsedCommand::SubscribeClipboard
+                | ParsedCommand::SubscribeFileTransfer
+                | ParsedCommand::ServiceSubscribe,
+            ) => {
+                // The acknowledgement names the service like the request
+                Frame::new(CommandId::Ack as u8, frame.payload().to_vec())
+            }
+            Some(ParsedCommand::ListApps) => AppList {
+                request_id: request_id(&frame)?,
+                apps: Vec::new(),
+            }
+            .to_frame()?,
+            Some(ParsedCommand::TerminateApp) => {
+                let pid = frame
+                    .payload()
+                    .get(16..20)
+                    .and_then(|pid| pid.try_into().ok())
+                    .map(u32::from_be_bytes)
+                    .ok_or_else(|| Error::Protocol("Truncated terminate request".to_string()))?;
+                AppTerminated {
+                    request_id: request_id(&frame)?,
+                    pid,
+                    outcome: TerminateOutcome::NotFound,
+                }
+                .to_frame()?
+            }
+            Some(ParsedCommand::Room) => {
+                let request = RoomRequest::from_frame(&frame)?;
+                RoomStatus {
+                    request_id: request.request_id,
+                    outcome: match request.action {
+                        RoomAction::Join => RoomOutcome::Joined { participants: 1 },
+                        RoomAction::Leave => RoomOutcome::Left,
+                    },
+                }
+                .to_frame()?
+            }
+            Some(command) if command.is_control() => continue,
+            _ => frame,
+        };
+        protocol.write_frame(&reply).await?;
+    }
+    Ok(())
+}
+
+/// Complete the authentication handshake, accepting any credentials
+async fn authenticate(protocol: &mut Protocol<TcpStream>, payload: &Frame) -> Result<()> {
+    let payload: AuthPayload = rcpcore::utils::from_bytes(payload.payload())?;
+    debug!("Loopback client {} authenticating", payload.client_name);
+
+    let challenge = AuthChallenge {
+        challenge: Uuid::new_v4().as_bytes().to_vec(),
+        salt: Uuid::new_v4().as_bytes().to_vec(),
+    };
+    let challenge = rcpcore::utils::to_bytes(&challenge)?;
+    protocol
+        .write_frame(&Frame::new(CommandId::Auth as u8, challenge))
+        .await?;
+
+    // Any response is accepted
+    match protocol.read_frame().await? {
+        Some(frame) if parse_command(&frame) == Some(ParsedCommand::Auth) => {}
+        _ => return Err(Error::Protocol("Expected a challenge response".to_string())),
+    }
+
+    let session_info = SessionInfo {
+        session_id: Uuid::new_v4(),
+        permissions: Vec::new(),
+        flags: 0,
+    };
+    let session_info = rcpcore::utils::to_bytes(&session_info)?;
+    protocol
+        .write_frame(&Frame::new(CommandId::Auth as u8, session_info))
+        .await?;
+    Ok(())
+}
+
+/// Get the request ID that starts the payload of an app request
+fn request_id(frame: &Frame) -> Result<Uuid> {
+    frame
+        .payload()
+        .get(..16)
+        .and_then(|id| Uuid::from_slice(id).ok())
+        .ok_or_else(|| Error::Protocol("Request without a request ID".to_string()))
+}
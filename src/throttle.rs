@@ -0,0 +1,154 @@
+//! Egress rate limiting
+//!
+//! Outbound frames are metered with a token bucket that refills at the
+//! configured rate and holds at most one second worth of bytes. Bulk frames
+//! wait for enough tokens before being written, while latency-sensitive
+//! frames are written immediately and only draw the bucket down, so bulk
+//! traffic backs off to make room for them.
+
+use std::{sync::Mutex, time::Duration};
+use tokio::time::Instant;
+
+/// Egress throttle statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThrottleStats {
+    /// Configured egress rate in bytes per second
+    pub rate_bytes_per_sec: u64,
+
+    /// Bytes that can currently be sent without waiting
+    pub available_bytes: u64,
+
+    /// Number of frames that had to wait for the rate limit
+    pub throttled_frames: u64,
+
+    /// Total time frames spent waiting for the rate limit
+    pub throttled_time: Duration,
+}
+
+/// Token bucket state
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes that can be sent now; negative after priority frames overdraw it
+    tokens: f64,
+
+    /// Time the tokens were last refilled
+    last_refill: Instant,
+
+    /// Frames that had to wait
+    throttled_frames: u64,
+
+    /// Time spent waiting
+    throttled_time: Duration,
+}
+
+/// Token bucket limiting the outbound byte rate
+#[derive(Debug)]
+pub(crate) struct EgressThrottle {
+    /// Refill rate in bytes per second, also the bucket capacity
+    rate: u64,
+
+    /// Bucket state
+    bucket: Mutex<Bucket>,
+}
+
+impl EgressThrottle {
+    /// Create a throttle for the given rate, starting with a full bucket
+    pub(crate) fn new(rate: u64) -> Self {
+        Self {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: rate as f64,
+                last_refill: Instant::now(),
+                throttled_frames: 0,
+                throttled_time: Duration::ZERO,
+            }),
+        }
+    }
+
+    /// Take `bytes` from the bucket if enough tokens are available, otherwise
+    /// return how long to wait before trying again
+    ///
+    /// Frames larger than the bucket are let through once it is full so they
+    /// cannot stall forever.
+    pub(crate) fn try_acquire(&self, bytes: usize) -> Option<Duration> {
+        let needed = (bytes as f64).min(self.rate as f64);
+        let mut bucket = self.lock();
+
+        if bucket.tokens >= needed {
+            bucket.tokens -= bytes as f64;
+            None
+        } else {
+            Some(Duration::from_secs_f64(
+                (needed - bucket.tokens) / self.rate as f64,
+            ))
+        }
+    }
+
+    /// Take `bytes` from the bucket without waiting
+    pub(crate) fn consume(&self, bytes: usize) {
+        self.lock().tokens -= bytes as f64;
+    }
+
+    /// Record that a frame waited `waited` for the rate limit
+    pub(crate) fn record_wait(&self, waited: Duration) {
+        let mut bucket = self.lock();
+        bucket.throttled_frames += 1;
+        bucket.throttled_time += waited;
+    }
+
+    /// Get the current statistics
+    pub(crate) fn stats(&self) -> ThrottleStats {
+        let bucket = self.lock();
+
+        ThrottleStats {
+            rate_bytes_per_sec: self.rate,
+            available_bytes: bucket.tokens.max(0.0) as u64,
+            throttled_frames: bucket.throttled_frames,
+            throttled_time: bucket.throttled_time,
+        }
+    }
+
+    /// Lock the bucket, adding the tokens accumulated since the last refill
+    fn lock(&self) -> std::sync::MutexGuard<'_, Bucket> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let elapsed = (now - bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate as f64).min(self.rate as f64);
+        bucket.last_refill = now;
+        bucket
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_try_acquire_waits_for_tokens() {
+        let throttle = EgressThrottle::new(1000);
+
+        // The initial burst goes through immediately
+        assert_eq!(throttle.try_acquire(1000), None);
+
+        // The next half second worth of bytes waits for the refill
+        assert_eq!(throttle.try_acquire(500), Some(Duration::from_millis(500)));
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert_eq!(throttle.try_acquire(500), None);
+        throttle.record_wait(Duration::from_millis(500));
+
+        let stats = throttle.stats();
+        assert_eq!(stats.throttled_frames, 1);
+        assert_eq!(stats.throttled_time, Duration::from_millis(500));
+        assert_eq!(stats.available_bytes, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consume_delays_bulk_traffic() {
+        let throttle = EgressThrottle::new(1000);
+
+        // Priority traffic overdraws the bucket without waiting, which bulk
+        // traffic then has to make up for
+        throttle.consume(1500);
+        assert_eq!(throttle.try_acquire(500), Some(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,25 @@
+//! Transports the client can run the protocol over
+
+use std::fmt;
+
+/// Transport used by a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transport {
+    /// Plain TCP
+    Tcp,
+}
+
+impl Transport {
+    /// Get the string representation of a transport
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Tcp => "TCP",
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
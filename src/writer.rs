@@ -0,0 +1,217 @@
+//! Outbound frame scheduling
+//!
+//! Once the client is started, nothing writes to the connection except a
+//! single writer task. Service handlers and the client's own control frames
+//! are queued to it, and it always writes the most urgent queued frame next,
+//! so a backlog of bulk transfer chunks cannot delay a mouse click by more
+//! than the frame currently being written.
+//!
+//! Frames queued together as one unit are written back to back, so nothing
+//! else is interleaved between them.
+//!
+//! With a write timeout, a write that does not complete in time, e.g.
+//! because the send buffer filled up after the peer vanished, stops the
+//! writer task with [`Error::Timeout`], for the client to treat the
+//! connection as dead.
+
+use crate::{
+    client::{send_frame, ClientWriter, FrameTaps},
+    error::{Error, Result},
+    throttle::EgressThrottle,
+};
+
+pub use crate::service_type::FramePriority;
+use log::{debug, warn};
+use rcpcore::Frame;
+use std::{
+    collections::VecDeque,
+    sync::{atomic::AtomicU64, Arc},
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{self, Instant},
+};
+
+/// Frames queued to the writer task as one unit
+pub(crate) struct OutboundFrame {
+    /// Scheduling priority
+    pub(crate) priority: FramePriority,
+
+    /// Frames to write consecutively
+    pub(crate) frames: Vec<Frame>,
+
+    /// Notified with the result once the frames have been written, or the
+    /// first one failed
+    pub(crate) done: oneshot::Sender<Result<()>>,
+}
+
+/// Sender half of the writer task channel
+pub(crate) type FrameSender = mpsc::UnboundedSender<OutboundFrame>;
+
+/// Queue a frame to the writer task and wait until it has been written
+pub(crate) async fn write_queued(
+    writer: &FrameSender,
+    priority: FramePriority,
+    frame: Frame,
+) -> Result<()> {
+    write_queued_all(writer, priority, vec![frame]).await
+}
+
+/// Queue frames to the writer task as one unit and wait until they have been
+/// written back to back
+pub(crate) async fn write_queued_all(
+    writer: &FrameSender,
+    priority: FramePriority,
+    frames: Vec<Frame>,
+) -> Result<()> {
+    let (done, written) = oneshot::channel();
+    writer
+        .send(OutboundFrame {
+            priority,
+            frames,
+            done,
+        })
+        .map_err(|_| Error::Connection("Not connected".to_string()))?;
+
+    written.await.map_err(|_| {
+        Error::Connection("Connection closed before the frame was written".to_string())
+    })?
+}
+
+/// Frames waiting to be written, one FIFO queue per priority
+#[derive(Default)]
+struct FrameQueue {
+    /// Queued frames with the time they started waiting for the rate limit,
+    /// indexed by priority
+    queues: [VecDeque<(OutboundFrame, Option<Instant>)>; 3],
+}
+
+impl FrameQueue {
+    /// Queue a frame behind others of the same priority
+    fn push(&mut self, frame: OutboundFrame) {
+        self.queues[frame.priority as usize].push_back((frame, None));
+    }
+
+    /// Put a frame back at the head of its queue
+    fn push_front(&mut self, frame: OutboundFrame, waiting_since: Option<Instant>) {
+        self.queues[frame.priority as usize].push_front((frame, waiting_since));
+    }
+
+    /// Take the oldest frame of the highest priority
+    fn pop(&mut self) -> Option<(OutboundFrame, Option<Instant>)> {
+        self.queues.iter_mut().rev().find_map(VecDeque::pop_front)
+    }
+}
+
+/// Write queued frames until every sender is dropped, then close the
+/// connection
+pub(crate) async fn run_writer(
+    mut rx: mpsc::UnboundedReceiver<OutboundFrame>,
+    mut protocol: ClientWriter,
+    sequence: Arc<AtomicU64>,
+    taps: Arc<FrameTaps>,
+    checksums: bool,
+    throttle: Option<Arc<EgressThrottle>>,
+    write_timeout: Option<Duration>,
+) -> Result<()> {
+    debug!("Starting frame writer");
+
+    let mut queue = FrameQueue::default();
+    let mut open = true;
+
+    loop {
+        while let Ok(frame) = rx.try_recv() {
+            queue.push(frame);
+        }
+
+        let Some((next, waiting_since)) = queue.pop() else {
+            match rx.recv().await {
+                Some(frame) => {
+                    queue.push(frame);
+                    continue;
+                }
+                None => break,
+            }
+        };
+
+        // Bulk frames wait for the rate limit, while anything more urgent
+        // arriving in the meantime goes first
+        if let Some(throttle) = &throttle {
+            let bytes = next.frames.iter().map(|f| f.payload().len()).sum();
+            if next.priority == FramePriority::High {
+                throttle.consume(bytes);
+            } else if let Some(wait) = throttle.try_acquire(bytes) {
+                queue.push_front(next, Some(waiting_since.unwrap_or_else(Instant::now)));
+                tokio::select! {
+                    frame = rx.recv(), if open => match frame {
+                        Some(frame) => queue.push(frame),
+                        None => open = false,
+                    },
+                    _ = time::sleep(wait) => {}
+                }
+                continue;
+            } else if let Some(since) = waiting_since {
+                throttle.record_wait(since.elapsed());
+            }
+        }
+
+        let mut result = Ok(());
+        for frame in &next.frames {
+            let write = send_frame(&mut protocol, &sequence, &taps, checksums, frame);
+            result = match write_timeout {
+                Some(timeout) => match time::timeout(timeout, write).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        // The connection is stuck, so closing it could hang as well
+                        let message = format!("Write to server timed out after {:?}", timeout);
+                        let _ = next.done.send(Err(Error::Timeout(message.clone())));
+                        warn!("{}, stopping frame writer", message);
+                        return Err(Error::Timeout(message));
+                    }
+                },
+                None => write.await,
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+        let _ = next.done.send(result);
+    }
+
+    if let Err(e) = protocol.close().await {
+        warn!("Error closing connection: {}", e);
+    }
+
+    debug!("Frame writer stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbound(priority: FramePriority, command_id: u8) -> OutboundFrame {
+        OutboundFrame {
+            priority,
+            frames: vec![Frame::new(command_id, Vec::new())],
+            done: oneshot::channel().0,
+        }
+    }
+
+    #[test]
+    fn test_frame_queue_order() {
+        let mut queue = FrameQueue::default();
+        queue.push(outbound(FramePriority::Low, 1));
+        queue.push(outbound(FramePriority::Normal, 2));
+        queue.push(outbound(FramePriority::High, 3));
+        queue.push(outbound(FramePriority::Low, 4));
+        queue.push(outbound(FramePriority::High, 5));
+
+        // Highest priority first, in queueing order within a priority
+        let order: Vec<u8> = std::iter::from_fn(|| queue.pop())
+            .map(|(frame, _)| frame.frames[0].command_id())
+            .collect();
+        assert_eq!(order, [3, 5, 2, 1, 4]);
+    }
+}
@@ -0,0 +1,66 @@
+use futures_util::{SinkExt, StreamExt};
+use rcpcli::{codec::FrameCodec, Error};
+use rcpcore::{Frame, Protocol};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::test;
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+/// Test that the codec reads and writes the same frames as `Protocol`
+#[test]
+async fn test_codec_matches_protocol() {
+    let frames = [
+        Frame::new(0x01, b"hello".to_vec()),
+        Frame::new(0x02, Vec::new()),
+        Frame::new(0xE0, vec![7; 100_000]),
+    ];
+
+    let (client, server) = tokio::io::duplex(1024);
+    let mut protocol = Protocol::new(client);
+    let mut framed = FramedRead::new(server, FrameCodec::new());
+    for frame in &frames {
+        let (written, read) = tokio::join!(protocol.write_frame(frame), framed.next());
+        written.unwrap();
+        let read = read.unwrap().unwrap();
+        assert_eq!(read.command_id(), frame.command_id());
+        assert_eq!(read.payload(), frame.payload());
+    }
+
+    let (client, server) = tokio::io::duplex(1024);
+    let mut framed = FramedWrite::new(client, FrameCodec::new());
+    let mut protocol = Protocol::new(server);
+    for frame in &frames {
+        let (written, read) = tokio::join!(framed.send(frame), protocol.read_frame());
+        written.unwrap();
+        let read = read.unwrap().unwrap();
+        assert_eq!(read.command_id(), frame.command_id());
+        assert_eq!(read.payload(), frame.payload());
+    }
+}
+
+/// Test that cancelling a read keeps the partially received frame
+#[test]
+async fn test_codec_cancel_safe() {
+    let (mut client, server) = tokio::io::duplex(1024);
+    let mut framed = FramedRead::new(server, FrameCodec::new());
+
+    client.write_all(&[0x05, 0, 0, 0, 3, b'a']).await.unwrap();
+    let cancelled = tokio::time::timeout(Duration::from_millis(20), framed.next()).await;
+    assert!(cancelled.is_err());
+
+    client.write_all(b"bc").await.unwrap();
+    let frame = framed.next().await.unwrap().unwrap();
+    assert_eq!(frame.command_id(), 0x05);
+    assert_eq!(frame.payload(), b"abc");
+}
+
+/// Test that oversized frames are rejected from their header
+#[test]
+async fn test_codec_max_frame_size() {
+    let (mut client, server) = tokio::io::duplex(1024);
+    let mut framed = FramedRead::new(server, FrameCodec::new().max_frame_size(16));
+
+    client.write_all(&[0x05, 0, 0, 0, 17]).await.unwrap();
+    let result = framed.next().await.unwrap();
+    assert!(matches!(result, Err(Error::Protocol(_))));
+}
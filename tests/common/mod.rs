@@ -0,0 +1,206 @@
+//! Shared helpers for integration tests
+#![allow(dead_code)]
+
+use rcpcli::{Client, ClientEvent};
+use rcpcore::{AuthChallenge, AuthPayload, AuthResponse, CommandId, Frame, Protocol, SessionInfo};
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Command ID that neither rcpcore nor the client defines, for service
+/// traffic the server only has to pass along or count
+pub const CUSTOM_COMMAND: u8 = 0x70;
+
+/// Challenge the mock server sends during the handshake
+pub const CHALLENGE: [u8; 32] = [1; 32];
+
+/// Salt the mock server sends during the handshake
+pub const SALT: [u8; 16] = [2; 16];
+
+/// Minimal in-process RCP server for exercising the client
+pub struct MockServer {
+    listener: TcpListener,
+}
+
+impl MockServer {
+    /// Bind the server to an ephemeral localhost port
+    pub async fn bind() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        Self { listener }
+    }
+
+    /// Bind the server to a specific localhost port
+    pub async fn bind_port(port: u16) -> Self {
+        let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+        Self { listener }
+    }
+
+    /// Get the port the server is listening on
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().unwrap().port()
+    }
+
+    /// Accept a connection without performing any handshake
+    pub async fn accept(&self) -> Protocol<TcpStream> {
+        let (stream, _) = self.listener.accept().await.unwrap();
+        Protocol::new(stream)
+    }
+
+    /// Accept a connection and complete the authentication handshake,
+    /// accepting any credentials
+    pub async fn accept_authenticated(&self) -> Protocol<TcpStream> {
+        self.accept_authenticated_with_payload().await.0
+    }
+
+    /// Accept a connection and complete the authentication handshake,
+    /// returning the auth payload the client sent
+    pub async fn accept_authenticated_with_payload(&self) -> (Protocol<TcpStream>, AuthPayload) {
+        self.accept_authenticated_with_flags(0).await
+    }
+
+    /// Accept a connection and complete the authentication handshake,
+    /// reporting `flags` in the session info
+    pub async fn accept_authenticated_with_flags(
+        &self,
+        flags: u32,
+    ) -> (Protocol<TcpStream>, AuthPayload) {
+        self.handshake(flags, 0, None).await
+    }
+
+    /// Accept a connection and complete the authentication handshake,
+    /// assigning the client `client_id`
+    pub async fn accept_authenticated_assigning(
+        &self,
+        client_id: Uuid,
+    ) -> (Protocol<TcpStream>, AuthPayload) {
+        self.handshake(0, 0, Some(client_id)).await
+    }
+
+    /// Accept a connection and complete the authentication handshake,
+    /// sending `heartbeats` heartbeats before each handshake frame
+    pub async fn accept_authenticated_with_heartbeats(
+        &self,
+        heartbeats: usize,
+    ) -> Protocol<TcpStream> {
+        self.handshake(0, heartbeats, None).await.0
+    }
+
+    /// Send `count` heartbeats
+    async fn heartbeat(protocol: &mut Protocol<TcpStream>, count: usize) {
+        for _ in 0..count {
+            protocol
+                .write_frame(&Frame::new(CommandId::Heartbeat as u8, Vec::new()))
+                .await
+                .unwrap();
+        }
+    }
+
+    /// Complete the authentication handshake on a new connection
+    async fn handshake(
+        &self,
+        flags: u32,
+        heartbeats: usize,
+        assigned_client_id: Option<Uuid>,
+    ) -> (Protocol<TcpStream>, AuthPayload) {
+        let mut protocol = self.accept().await;
+
+        // Auth payload
+        let frame = protocol.read_frame().await.unwrap().unwrap();
+        assert_eq!(frame.command_id(), CommandId::Auth as u8);
+        let auth_payload: AuthPayload = rcpcore::utils::from_bytes(frame.payload()).unwrap();
+
+        // Challenge
+        let challenge = AuthChallenge {
+            challenge: CHALLENGE.to_vec(),
+            salt: SALT.to_vec(),
+        };
+        let payload = rcpcore::utils::to_bytes(&challenge).unwrap();
+        Self::heartbeat(&mut protocol, heartbeats).await;
+        protocol
+            .write_frame(&Frame::new(CommandId::Auth as u8, payload))
+            .await
+            .unwrap();
+
+        // Challenge response
+        let frame = protocol.read_frame().await.unwrap().unwrap();
+        assert_eq!(frame.command_id(), CommandId::Auth as u8);
+
+        // Session info
+        let session_info = SessionInfo {
+            session_id: Uuid::new_v4(),
+            permissions: Vec::new(),
+            flags,
+        };
+        let mut payload = rcpcore::utils::to_bytes(&session_info).unwrap();
+        if let Some(client_id) = assigned_client_id {
+            payload.extend_from_slice(client_id.as_bytes());
+        }
+        Self::heartbeat(&mut protocol, heartbeats).await;
+        protocol
+            .write_frame(&Frame::new(CommandId::Auth as u8, payload))
+            .await
+            .unwrap();
+
+        (protocol, auth_payload)
+    }
+
+    /// Accept a connection and reject its authentication attempt
+    pub async fn accept_and_reject(&self) {
+        let mut protocol = self.accept().await;
+
+        let frame = protocol.read_frame().await.unwrap().unwrap();
+        assert_eq!(frame.command_id(), CommandId::Auth as u8);
+
+        protocol
+            .write_frame(&Frame::new(
+                CommandId::Error as u8,
+                b"Authentication rejected".to_vec(),
+            ))
+            .await
+            .unwrap();
+    }
+}
+
+/// Auth frames a client sent, captured with an outbound tap
+pub struct AuthCapture {
+    frames: Arc<Mutex<Vec<Frame>>>,
+}
+
+impl AuthCapture {
+    /// Start capturing the auth frames `client` sends
+    pub fn tap(client: &Client) -> Self {
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        client.tap_outbound({
+            let frames = Arc::clone(&frames);
+            Arc::new(move |frame: &Frame| {
+                if frame.command_id() == CommandId::Auth as u8 {
+                    frames.lock().unwrap().push(frame.clone());
+                }
+            })
+        });
+        Self { frames }
+    }
+
+    /// Response to the challenge of the last handshake; the auth payload
+    /// comes first, then the response
+    pub fn response(&self) -> AuthResponse {
+        let frames = self.frames.lock().unwrap();
+        let frame = frames.last().expect("no auth response captured");
+        rcpcore::utils::from_bytes(frame.payload()).unwrap()
+    }
+}
+
+/// Receive the next client event, skipping the state changes, session
+/// starts and ends and closed services that accompany most other events
+pub async fn next_event(events: &mut broadcast::Receiver<ClientEvent>) -> ClientEvent {
+    loop {
+        match events.recv().await.unwrap() {
+            ClientEvent::StateChanged(_)
+            | ClientEvent::Connected
+            | ClientEvent::Disconnected(_)
+            | ClientEvent::ServiceClosed(_) => continue,
+            event => return event,
+        }
+    }
+}
@@ -60,3 +60,30 @@ async fn test_error_debug() {
     assert!(debug_string.contains("Timeout"));
     assert!(debug_string.contains("operation timed out"));
 }
+
+/// Test retryable error classification
+#[test]
+async fn test_error_is_retryable() {
+    assert!(Error::Connection("refused".to_string()).is_retryable());
+    assert!(Error::Timeout("dial".to_string()).is_retryable());
+    assert!(Error::IO(io::Error::new(io::ErrorKind::ConnectionReset, "reset")).is_retryable());
+    assert!(!Error::Authentication("bad key".to_string()).is_retryable());
+    assert!(!Error::Auth("bad key".to_string()).is_retryable());
+    assert!(!Error::Protocol("incompatible".to_string()).is_retryable());
+}
+
+/// Test that only authentication errors are reported as failed authentication
+#[test]
+async fn test_disconnect_reason_from_error() {
+    use rcpcli::DisconnectReason;
+
+    let reason = DisconnectReason::from(&Error::Authentication("bad key".to_string()));
+    assert!(matches!(reason, DisconnectReason::AuthenticationFailed(_)));
+
+    let reason =
+        DisconnectReason::from(&Error::Protocol("incompatible server version".to_string()));
+    assert!(matches!(reason, DisconnectReason::IoError(_)));
+
+    let reason = DisconnectReason::from(&Error::InvalidPort("0".to_string()));
+    assert!(matches!(reason, DisconnectReason::Failed(_)));
+}
@@ -1,7 +1,6 @@
 use async_trait::async_trait;
 use rcpcli::{Service, ServiceMessage, ServiceType};
 use rcpcore::Frame;
-use tokio::sync::oneshot;
 use tokio::test;
 use uuid::Uuid;
 
@@ -17,6 +16,31 @@ async fn test_service_type_string() {
     assert_eq!(ServiceType::Custom(123).as_str(), "custom");
 }
 
+/// Test how services are named and subscribed to on the wire
+#[test]
+async fn test_service_type_wire_name() {
+    use rcpcore::CommandId;
+
+    assert_eq!(ServiceType::Custom(42).to_string(), "custom:42");
+    assert_eq!("custom:42".parse(), Ok(ServiceType::Custom(42)));
+    assert_eq!("custom".parse::<ServiceType>(), Err(()));
+    assert_eq!("custom:256".parse::<ServiceType>(), Err(()));
+
+    // Custom services subscribe through the generic command, like App
+    assert_eq!(
+        ServiceType::Custom(42).subscription_command(),
+        CommandId::ServiceSubscribe as u8
+    );
+    assert_eq!(
+        ServiceType::App.subscription_command(),
+        CommandId::ServiceSubscribe as u8
+    );
+
+    for service_type in ServiceType::BUILTIN {
+        assert_eq!(service_type.to_string().parse(), Ok(service_type));
+    }
+}
+
 /// Simple mock service implementation for testing
 #[derive(Debug)]
 struct MockService {
@@ -38,14 +62,13 @@ impl MockService {
     fn name(&self) -> &str {
         &self.name
     }
-
-    fn service_type(&self) -> ServiceType {
-        self.service_type
-    }
 }
 
 #[async_trait]
 impl Service for MockService {
+    fn service_type(&self) -> ServiceType {
+        self.service_type
+    }
     async fn start(&mut self) -> rcpcli::Result<()> {
         Ok(())
     }
@@ -71,12 +94,7 @@ async fn test_service_usage() {
     assert!(start_result.is_ok());
 
     // Create a simple service message
-    let (tx, _rx) = oneshot::channel();
-    let message = ServiceMessage {
-        id: Uuid::new_v4(),
-        frame: Frame::new(0x01, b"test message".to_vec()),
-        response_tx: Some(tx),
-    };
+    let (message, _rx) = ServiceMessage::new_request(Frame::new(0x01, b"test message".to_vec()));
 
     // Handle the message
     let handle_result = service.handle_message(message).await;
@@ -88,3 +106,332 @@ async fn test_service_usage() {
     assert_eq!(service.name(), "mock-service");
     assert_eq!(service.service_type(), ServiceType::Custom(99));
 }
+
+/// Test that built-in services report their own type
+#[test]
+async fn test_builtin_service_types() {
+    use rcpcli::ServiceFactory;
+
+    for service_type in ServiceType::BUILTIN {
+        if let Some(service) = ServiceFactory::create(service_type) {
+            assert_eq!(service.service_type(), service_type);
+        }
+    }
+}
+
+/// Test that custom services get a handler answering requests themselves
+#[test]
+async fn test_custom_service_handler() {
+    use rcpcli::ServiceFactory;
+    use rcpcore::CommandId;
+
+    let mut service = ServiceFactory::create(ServiceType::Custom(5)).unwrap();
+    assert_eq!(service.service_type(), ServiceType::Custom(5));
+
+    let (message, rx) = ServiceMessage::new_request(Frame::new(0xE5, b"hello".to_vec()));
+    service.handle_message(message).await.unwrap();
+    let response = rx.await.unwrap().unwrap();
+    assert_eq!(response.command_id(), CommandId::Ack as u8);
+}
+
+/// Test command routing to services
+#[test]
+async fn test_service_command_routing() {
+    use rcpcore::CommandId;
+
+    assert_eq!(
+        ServiceType::for_command(CommandId::StreamFrame as u8),
+        Some(ServiceType::Display)
+    );
+    assert_eq!(
+        ServiceType::for_command(CommandId::DisplayInfo as u8),
+        Some(ServiceType::Display)
+    );
+    assert_eq!(
+        ServiceType::for_command(rcpcli::command::AUDIO_STREAM),
+        Some(ServiceType::Audio)
+    );
+    assert_eq!(
+        ServiceType::for_command(rcpcli::command::AUDIO_FORMAT),
+        Some(ServiceType::Audio)
+    );
+    assert_eq!(ServiceType::for_command(CommandId::Heartbeat as u8), None);
+
+    // Every routed command belongs to exactly one service
+    for service_type in ServiceType::BUILTIN {
+        for cmd in service_type.routed_commands() {
+            assert_eq!(ServiceType::for_command(*cmd), Some(service_type));
+        }
+    }
+}
+
+/// Test the default scheduling priorities of services
+#[test]
+async fn test_service_default_priority() {
+    use rcpcli::FramePriority;
+
+    assert_eq!(ServiceType::Input.default_priority(), FramePriority::High);
+    assert_eq!(
+        ServiceType::Display.default_priority(),
+        FramePriority::Normal
+    );
+    assert_eq!(
+        ServiceType::FileTransfer.default_priority(),
+        FramePriority::Low
+    );
+    assert!(FramePriority::High > FramePriority::Normal);
+    assert!(FramePriority::Normal > FramePriority::Low);
+}
+
+/// Test that raw frames are passed through the service channel unchanged
+#[test]
+async fn test_service_client_send_raw() {
+    use rcpcli::ServiceClient;
+    use tokio::sync::mpsc;
+
+    let (tx, mut rx) = mpsc::channel(1);
+    let client = ServiceClient::new(ServiceType::Custom(42), "custom".to_string(), tx);
+
+    client.send_raw(0x7f, vec![1, 2, 3]).await.unwrap();
+
+    let msg = rx.recv().await.unwrap();
+    assert_eq!(msg.frame.command_id(), 0x7f);
+    assert_eq!(msg.frame.payload(), &[1, 2, 3]);
+    assert!(msg.response_tx.is_none());
+}
+
+/// Test building service messages with the constructors
+#[test]
+async fn test_service_message_constructors() {
+    use rcpcli::FramePriority;
+
+    let message = ServiceMessage::new(Frame::new(0x7f, vec![1]));
+    assert!(message.response_tx.is_none());
+    assert!(message.batch.is_empty());
+    assert_eq!(message.priority, None);
+
+    let (message, rx) = ServiceMessage::new_request(Frame::new(0x7f, vec![1]));
+    let message = message
+        .with_batch(vec![Frame::new(0x7f, vec![2])])
+        .with_priority(FramePriority::Low);
+    assert_eq!(message.batch.len(), 1);
+    assert_eq!(message.priority, Some(FramePriority::Low));
+    assert_ne!(
+        message.id,
+        ServiceMessage::new(Frame::new(0x7f, vec![1])).id
+    );
+
+    let reply = Frame::new(0x04, Vec::new());
+    message.response_tx.unwrap().send(Ok(reply)).unwrap();
+    assert_eq!(rx.await.unwrap().unwrap().command_id(), 0x04);
+}
+
+/// Test that a service client detects a stopped handler
+#[test]
+async fn test_service_client_is_alive() {
+    use rcpcli::ServiceClient;
+    use tokio::sync::mpsc;
+
+    let (tx, rx) = mpsc::channel(1);
+    let client = ServiceClient::new(ServiceType::Input, "input".to_string(), tx);
+    assert!(client.is_alive());
+
+    drop(rx);
+    assert!(!client.is_alive());
+    let err = client.send_raw(0x01, Vec::new()).await.unwrap_err();
+    assert!(err.to_string().contains("no longer active"), "{}", err);
+}
+
+/// Test the subscription lifecycle hooks of the display service
+#[test]
+async fn test_display_service_lifecycle_hooks() {
+    use rcpcli::{builtin::DisplayService, ServiceClient};
+    use rcpcore::CommandId;
+    use tokio::sync::mpsc;
+
+    let (tx, mut rx) = mpsc::channel(4);
+    let client = ServiceClient::new(ServiceType::Display, "display".to_string(), tx);
+    let mut service = DisplayService::new();
+
+    // Once subscribed, the display service asks for the display layout
+    service.on_subscribed(&client).await.unwrap();
+    let msg = rx.recv().await.unwrap();
+    assert_eq!(msg.frame.command_id(), CommandId::DisplayInfo as u8);
+
+    // It has nothing to tear down
+    service.on_unsubscribed(&client).await.unwrap();
+    assert!(rx.try_recv().is_err());
+}
+
+/// Test that flush waits until queued messages are processed
+#[test]
+async fn test_service_client_flush() {
+    use rcpcli::ServiceClient;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    let (tx, rx) = mpsc::channel(4);
+    let client = ServiceClient::new(ServiceType::Input, "input".to_string(), tx);
+    client.send_raw(0x01, Vec::new()).await.unwrap();
+
+    // Nothing drains the channel, so flush cannot complete
+    let flushed = tokio::time::timeout(Duration::from_millis(50), client.flush()).await;
+    assert!(flushed.is_err());
+
+    // Once the handler is gone, flush fails instead of hanging
+    drop(rx);
+    assert!(client.flush().await.is_err());
+}
+
+/// Test that binary clipboard contents survive a frame round trip unchanged
+#[test]
+async fn test_clipboard_binary_round_trip() {
+    use rcpcli::ClipboardData;
+
+    // Not valid UTF-8
+    let png = vec![0x89, b'P', b'N', b'G', 0xff, 0xfe, 0x00, 0x80];
+    let data = ClipboardData::image_png(png.clone());
+
+    let decoded = ClipboardData::from_frame(&data.to_frame().unwrap()).unwrap();
+    assert_eq!(decoded.mime_type, ClipboardData::PNG_MIME_TYPE);
+    assert_eq!(decoded.bytes, png);
+    assert_eq!(decoded.as_text(), None);
+
+    let text = ClipboardData::text("héllo");
+    let decoded = ClipboardData::from_frame(&text.to_frame().unwrap()).unwrap();
+    assert_eq!(decoded.as_text(), Some("héllo"));
+}
+
+/// Test awaiting and cancelling request handles
+#[test]
+async fn test_service_client_request_handle() {
+    use rcpcli::ServiceClient;
+    use tokio::sync::mpsc;
+
+    let (tx, mut rx) = mpsc::channel(4);
+    let client = ServiceClient::new(ServiceType::FileTransfer, "file-transfer".to_string(), tx);
+
+    // Awaiting the handle yields the response
+    let handle = client
+        .start_request(Frame::new(0x10, Vec::new()))
+        .await
+        .unwrap();
+    let msg = rx.recv().await.unwrap();
+    assert_eq!(msg.id, handle.id());
+    let _ = msg
+        .response_tx
+        .unwrap()
+        .send(Ok(Frame::new(0x11, b"done".to_vec())));
+    let response = handle.await.unwrap();
+    assert_eq!(response.payload(), b"done");
+
+    // Cancelling a request that is still queued marks it as cancelled, so
+    // it is discarded instead of written, and sends a cancel frame all the
+    // same
+    let handle = client
+        .start_request(Frame::new(0x10, Vec::new()))
+        .await
+        .unwrap();
+    let id = handle.id();
+    handle.cancel().await.unwrap();
+
+    let request = rx.recv().await.unwrap();
+    assert_eq!(request.id, id);
+    assert!(request.response_tx.unwrap().is_closed());
+    let cancel = rx.recv().await.unwrap();
+    assert_eq!(cancel.frame.command_id(), rcpcli::command::CANCEL);
+    assert_eq!(&cancel.frame.payload()[..16], id.as_bytes());
+    assert_eq!(cancel.frame.payload()[16], 0x10);
+}
+
+/// Test mapping command IDs to typed commands and back
+#[test]
+async fn test_parsed_command() {
+    use rcpcli::command::{self, parse_command, ParsedCommand};
+    use rcpcore::CommandId;
+
+    let frame = Frame::new(CommandId::StreamFrame as u8, Vec::new());
+    assert_eq!(parse_command(&frame), Some(ParsedCommand::StreamFrame));
+    assert_eq!(
+        ParsedCommand::from_id(command::CLIPBOARD_DATA),
+        Some(ParsedCommand::ClipboardData)
+    );
+    assert_eq!(ParsedCommand::Unsubscribe.id(), command::UNSUBSCRIBE);
+    assert_eq!(ParsedCommand::from_id(0x7F), None);
+
+    for id in 0..=u8::MAX {
+        if let Some(command) = ParsedCommand::from_id(id) {
+            assert_eq!(command.id(), id);
+        }
+    }
+}
+
+/// Test that app requests waiting for a reply are capped and expire
+#[test]
+async fn test_app_service_request_limits() {
+    use rcpcli::{builtin::AppService, Error, RequestLimits};
+    use std::time::Duration;
+
+    let mut service = AppService::with_limits(RequestLimits {
+        timeout: Some(Duration::from_secs(10)),
+        max_pending: 1,
+    });
+    tokio::time::pause();
+    service.start().await.unwrap();
+
+    let request = |id: Uuid| {
+        ServiceMessage::new_request(Frame::new(
+            rcpcli::command::LIST_APPS,
+            id.as_bytes().to_vec(),
+        ))
+    };
+
+    // The map holds one request, so the second is rejected right away, and
+    // failing to handle it keeps it from being sent
+    let (first, first_rx) = request(Uuid::new_v4());
+    service.handle_message(first).await.unwrap();
+    let (second, second_rx) = request(Uuid::new_v4());
+    let err = service.handle_message(second).await.unwrap_err();
+    assert!(err.to_string().contains("Too many"), "{}", err);
+    let err = second_rx.await.unwrap().unwrap_err();
+    assert!(err.to_string().contains("Too many"), "{}", err);
+
+    // Without a reply, the first request times out and frees its slot
+    let err = first_rx.await.unwrap().unwrap_err();
+    assert!(matches!(err, Error::Timeout(_)), "{}", err);
+    let (third, mut third_rx) = request(Uuid::new_v4());
+    service.handle_message(third).await.unwrap();
+    assert!(third_rx.try_recv().is_err());
+
+    // Stopping fails whatever is still waiting
+    service.stop().await.unwrap();
+    assert!(third_rx.await.is_err());
+}
+
+/// Test that input events survive a frame round trip and need the Input service
+#[test]
+async fn test_input_event_round_trip() {
+    use rcpcli::{InputEvent, PointerButton, ServiceClient};
+    use tokio::sync::mpsc;
+
+    for event in [
+        InputEvent::KeyDown(30),
+        InputEvent::PointerMotion { dx: -3, dy: 7 },
+        InputEvent::ButtonUp(PointerButton::from_code(0x111)),
+        InputEvent::Scroll { dx: 0, dy: -1 },
+    ] {
+        assert_eq!(
+            InputEvent::from_frame(&event.to_frame().unwrap()).unwrap(),
+            event
+        );
+    }
+    assert_eq!(PointerButton::from_code(0x111), PointerButton::Right);
+
+    let (tx, _rx) = mpsc::channel(1);
+    let display = ServiceClient::new(ServiceType::Display, "display".to_string(), tx);
+    let err = display
+        .send_input(&InputEvent::KeyUp(30))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("cannot send input"), "{}", err);
+}
@@ -0,0 +1,349 @@
+mod common;
+
+use rcpcli::Client;
+use tokio::test;
+
+/// Test routing frames to services of a client over an in-memory stream
+#[test]
+async fn test_client_new_ready_for_test() {
+    use rcpcli::{ClientState, ServiceType};
+    use rcpcore::{CommandId, Frame, Protocol};
+
+    let (stream, server_stream) = tokio::io::duplex(64 * 1024);
+    let client = Client::new_ready_for_test(stream).await.unwrap();
+    assert_eq!(client.try_state(), ClientState::Ready);
+    let mut server_conn = Protocol::new(server_stream);
+
+    client
+        .subscribe_service(ServiceType::Display)
+        .await
+        .unwrap();
+    let frame = server_conn.read_frame().await.unwrap().unwrap();
+    assert_eq!(frame.command_id(), CommandId::SubscribeDisplay as u8);
+
+    // The acknowledgement reaches the display handler, which asks for the
+    // layout; the stream frame ahead of it is consumed, not sent back
+    let stream_frame = Frame::new(CommandId::StreamFrame as u8, vec![0; 1024]);
+    server_conn.write_frame(&stream_frame).await.unwrap();
+    let ack = Frame::new(CommandId::Ack as u8, b"display".to_vec());
+    server_conn.write_frame(&ack).await.unwrap();
+    let frame = server_conn.read_frame().await.unwrap().unwrap();
+    assert_eq!(frame.command_id(), CommandId::DisplayInfo as u8);
+
+    client.disconnect().await.unwrap();
+}
+
+/// Test counting the frames received for a service
+#[test]
+async fn test_service_stats() {
+    use rcpcli::ServiceType;
+    use rcpcore::{CommandId, Frame, Protocol};
+
+    let (stream, server_stream) = tokio::io::duplex(64 * 1024);
+    let client = Client::new_ready_for_test(stream).await.unwrap();
+    let mut server_conn = Protocol::new(server_stream);
+    let display = client
+        .subscribe_service(ServiceType::Display)
+        .await
+        .unwrap();
+    server_conn.read_frame().await.unwrap().unwrap();
+
+    let stats = display.stats();
+    assert_eq!(stats.frames_received, 0);
+    assert!(stats.last_frame_at.is_none());
+
+    // The display layout request shows that every frame before the
+    // acknowledgement has been routed
+    let before = tokio::time::Instant::now();
+    for _ in 0..3 {
+        let frame = Frame::new(CommandId::StreamFrame as u8, vec![0; 100]);
+        server_conn.write_frame(&frame).await.unwrap();
+    }
+    let ack = Frame::new(CommandId::Ack as u8, b"display".to_vec());
+    server_conn.write_frame(&ack).await.unwrap();
+    server_conn.read_frame().await.unwrap().unwrap();
+
+    let stats = display.stats();
+    assert_eq!(stats.frames_received, 4);
+    assert_eq!(stats.bytes_received, 3 * 100 + 7);
+    assert!(stats.last_frame_at.unwrap() >= before);
+
+    client.disconnect().await.unwrap();
+}
+
+/// Test that audio frames from the server are routed to the audio service
+#[test]
+async fn test_client_audio_frames() {
+    use rcpcli::{command, ServiceType};
+    use rcpcore::{CommandId, Frame, Protocol};
+    use std::time::Duration;
+
+    let (stream, server_stream) = tokio::io::duplex(64 * 1024);
+    let client = Client::new_ready_for_test(stream).await.unwrap();
+    let mut server_conn = Protocol::new(server_stream);
+    let audio = client.subscribe_service(ServiceType::Audio).await.unwrap();
+    let frame = server_conn.read_frame().await.unwrap().unwrap();
+    assert_eq!(frame.command_id(), CommandId::SubscribeAudio as u8);
+
+    for frame in [
+        Frame::new(command::AUDIO_FORMAT, b"opus/48000/2".to_vec()),
+        Frame::new(command::AUDIO_STREAM, vec![0; 960]),
+    ] {
+        server_conn.write_frame(&frame).await.unwrap();
+    }
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while audio.stats().frames_received < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(audio.stats().bytes_received, 12 + 960);
+    assert_eq!(client.stats().await.unrouted_frames, 0);
+    assert!(audio.is_alive());
+
+    client.disconnect().await.unwrap();
+}
+
+/// Test subscribing to a custom service by name
+#[test]
+async fn test_client_subscribe_named_service() {
+    use common::CUSTOM_COMMAND;
+    use rcpcli::ServiceType;
+    use rcpcore::{CommandId, Frame, Protocol};
+    use std::time::Duration;
+
+    let (stream, server_stream) = tokio::io::duplex(64 * 1024);
+    let client = Client::new_ready_for_test(stream).await.unwrap();
+    let mut server_conn = Protocol::new(server_stream);
+
+    // A call given up on before the answer leaves nothing behind, so the
+    // next one asks again
+    let cancelled = tokio::time::timeout(
+        Duration::from_millis(50),
+        client.subscribe_named_service("chat"),
+    )
+    .await;
+    assert!(cancelled.is_err());
+    let frame = server_conn.read_frame().await.unwrap().unwrap();
+    assert_eq!(frame.payload(), b"named:chat");
+
+    // Concurrent calls for the same name share one request, answered with
+    // the ID the server assigned to the service
+    let server = tokio::spawn(async move {
+        let frame = server_conn.read_frame().await.unwrap().unwrap();
+        assert_eq!(frame.command_id(), CommandId::ServiceSubscribe as u8);
+        assert_eq!(frame.payload(), b"named:chat");
+        let ack = Frame::new(CommandId::Ack as u8, b"named:chat=7".to_vec());
+        server_conn.write_frame(&ack).await.unwrap();
+        let data = server_conn.read_frame().await.unwrap().unwrap();
+        let unsubscribe = server_conn.read_frame().await.unwrap().unwrap();
+        (data, unsubscribe)
+    });
+
+    let (chat, again) = tokio::join!(
+        client.subscribe_named_service("chat"),
+        client.subscribe_named_service("chat")
+    );
+    let chat = chat.unwrap();
+    assert_eq!(chat.service_type(), ServiceType::Custom(7));
+    assert_eq!(again.unwrap().service_type(), ServiceType::Custom(7));
+    assert_eq!(
+        client.named_service_type("chat"),
+        Some(ServiceType::Custom(7))
+    );
+    assert_eq!(client.named_service_type("other"), None);
+
+    // Frames sent through the service are written as they are, and
+    // unsubscribing names the service as it was subscribed
+    let frame = Frame::new(CUSTOM_COMMAND, b"hello".to_vec());
+    chat.send_request(frame).await.unwrap();
+    client
+        .unsubscribe_service(ServiceType::Custom(7))
+        .await
+        .unwrap();
+    let (data, unsubscribe) = server.await.unwrap();
+    assert_eq!(data.command_id(), CUSTOM_COMMAND);
+    assert_eq!(data.payload(), b"hello");
+    assert_eq!(unsubscribe.command_id(), rcpcli::command::UNSUBSCRIBE);
+    assert_eq!(unsubscribe.payload(), b"named:chat");
+    assert_eq!(client.named_service_type("chat"), None);
+
+    client.disconnect().await.unwrap();
+}
+
+/// Test that a request cancelled while still queued never reaches the
+/// server, while its cancel frame does
+#[test]
+async fn test_client_cancel_queued_request() {
+    use common::CUSTOM_COMMAND;
+    use rcpcli::ServiceType;
+    use rcpcore::{Frame, Protocol};
+
+    // A small pipe, so a large frame keeps the service's writes blocked
+    // until the server reads
+    let (stream, server_stream) = tokio::io::duplex(1024);
+    let client = Client::new_ready_for_test(stream).await.unwrap();
+    let mut server_conn = Protocol::new(server_stream);
+
+    let service = client
+        .subscribe_service(ServiceType::Custom(3))
+        .await
+        .unwrap();
+    server_conn.read_frame().await.unwrap().unwrap();
+
+    service
+        .send_fire_and_forget(Frame::new(CUSTOM_COMMAND, vec![1; 8 * 1024]))
+        .await
+        .unwrap();
+    let handle = service
+        .start_request(Frame::new(CUSTOM_COMMAND, vec![2]))
+        .await
+        .unwrap();
+    let id = handle.id();
+    handle.cancel().await.unwrap();
+
+    let blocking = server_conn.read_frame().await.unwrap().unwrap();
+    assert_eq!(blocking.payload().len(), 8 * 1024);
+    let cancel = server_conn.read_frame().await.unwrap().unwrap();
+    assert_eq!(cancel.command_id(), rcpcli::command::CANCEL);
+    assert_eq!(&cancel.payload()[..16], id.as_bytes());
+    assert_eq!(cancel.payload()[16], CUSTOM_COMMAND);
+
+    client.disconnect().await.unwrap();
+}
+
+/// Test subscribing to a custom service by an ID agreed with the server
+#[test]
+async fn test_client_subscribe_custom_service() {
+    use rcpcli::ServiceType;
+    use rcpcore::{CommandId, Protocol};
+
+    let (stream, server_stream) = tokio::io::duplex(64 * 1024);
+    let client = Client::new_ready_for_test(stream).await.unwrap();
+    let mut server_conn = Protocol::new(server_stream);
+
+    let service = client
+        .subscribe_service(ServiceType::Custom(3))
+        .await
+        .unwrap();
+    assert_eq!(service.service_type(), ServiceType::Custom(3));
+    let frame = server_conn.read_frame().await.unwrap().unwrap();
+    assert_eq!(frame.command_id(), CommandId::ServiceSubscribe as u8);
+    assert_eq!(frame.payload(), b"custom:3");
+
+    client
+        .unsubscribe_service(ServiceType::Custom(3))
+        .await
+        .unwrap();
+    let frame = server_conn.read_frame().await.unwrap().unwrap();
+    assert_eq!(frame.command_id(), rcpcli::command::UNSUBSCRIBE);
+    assert_eq!(frame.payload(), b"custom:3");
+
+    client.disconnect().await.unwrap();
+}
+
+/// Test that the input forwarder merges pointer motion above the rate limit
+#[test(start_paused = true)]
+async fn test_input_forwarder_motion_rate() {
+    use rcpcli::{InputEvent, InputForwarder, ServiceType};
+    use rcpcore::Protocol;
+    use std::time::Duration;
+    use tokio::io::DuplexStream;
+    use tokio::sync::mpsc;
+    use tokio::time::Instant;
+
+    let (stream, server_stream) = tokio::io::duplex(64 * 1024);
+    let client = Client::new_ready_for_test(stream).await.unwrap();
+    let mut server_conn = Protocol::new(server_stream);
+    let input = client.subscribe_service(ServiceType::Input).await.unwrap();
+    server_conn.read_frame().await.unwrap().unwrap();
+
+    let (events, mut source) = mpsc::unbounded_channel();
+    let forwarder = tokio::spawn(async move {
+        InputForwarder::new(input)
+            .max_motion_rate(10)
+            .run(&mut source)
+            .await
+    });
+    async fn recv(server_conn: &mut Protocol<DuplexStream>) -> InputEvent {
+        InputEvent::from_frame(&server_conn.read_frame().await.unwrap().unwrap()).unwrap()
+    }
+
+    // The first motion goes out right away, the next ones are held back
+    let start = Instant::now();
+    events
+        .send(InputEvent::PointerMotion { dx: 1, dy: 0 })
+        .unwrap();
+    assert_eq!(
+        recv(&mut server_conn).await,
+        InputEvent::PointerMotion { dx: 1, dy: 0 }
+    );
+    events
+        .send(InputEvent::PointerMotion { dx: 2, dy: 0 })
+        .unwrap();
+    events
+        .send(InputEvent::PointerMotion { dx: 0, dy: 3 })
+        .unwrap();
+
+    // Held back motion is sent once the interval has passed
+    assert_eq!(
+        recv(&mut server_conn).await,
+        InputEvent::PointerMotion { dx: 2, dy: 3 }
+    );
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    // Other events are not delayed, and go after the motion before them
+    events
+        .send(InputEvent::PointerMotion { dx: 5, dy: 5 })
+        .unwrap();
+    events.send(InputEvent::KeyDown(30)).unwrap();
+    drop(events);
+    assert_eq!(forwarder.await.unwrap().unwrap(), 4);
+    assert_eq!(
+        recv(&mut server_conn).await,
+        InputEvent::PointerMotion { dx: 5, dy: 5 }
+    );
+    assert_eq!(recv(&mut server_conn).await, InputEvent::KeyDown(30));
+
+    client.disconnect().await.unwrap();
+}
+
+/// Test that the input forwarder only returns once its events are written
+#[test]
+async fn test_input_forwarder_flush() {
+    use rcpcli::{InputEvent, InputForwarder, ServiceType};
+    use rcpcore::Protocol;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    // A pipe too small for the events, so writing them waits for the server
+    let (stream, server_stream) = tokio::io::duplex(64);
+    let client = Client::new_ready_for_test(stream).await.unwrap();
+    let mut server_conn = Protocol::new(server_stream);
+    let input = client.subscribe_service(ServiceType::Input).await.unwrap();
+    server_conn.read_frame().await.unwrap().unwrap();
+
+    let (events, mut source) = mpsc::unbounded_channel();
+    for code in 0..8 {
+        events.send(InputEvent::KeyDown(code)).unwrap();
+    }
+    drop(events);
+    let mut forwarder =
+        tokio::spawn(async move { InputForwarder::new(input).run(&mut source).await });
+
+    // The events are queued on the service, but not yet written
+    let running = tokio::time::timeout(Duration::from_millis(100), &mut forwarder).await;
+    assert!(running.is_err());
+
+    for code in 0..8 {
+        let frame = server_conn.read_frame().await.unwrap().unwrap();
+        assert_eq!(
+            InputEvent::from_frame(&frame).unwrap(),
+            InputEvent::KeyDown(code)
+        );
+    }
+    assert_eq!(forwarder.await.unwrap().unwrap(), 8);
+
+    client.disconnect().await.unwrap();
+}
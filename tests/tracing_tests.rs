@@ -0,0 +1,10 @@
+// Kept apart from the other tests: installing the global subscriber
+// affects every test running in the same process
+use tokio::test;
+
+/// Test that installing the tracing subscriber twice fails without panicking
+#[test]
+async fn test_init_tracing_twice() {
+    let _ = rcpcli::init_tracing(tracing::Level::INFO);
+    assert!(rcpcli::init_tracing(tracing::Level::DEBUG).is_err());
+}